@@ -35,6 +35,78 @@ const COMMANDS: &[&str] = &[
     "get_matrix_to_permalink_for_room",
     "android_share_matrix_media",
     "register_notifications",
+    "schedule_text_message",
+    "schedule_media_message",
+    "get_scheduled_messages",
+    "edit_scheduled_message",
+    "cancel_scheduled_message",
+    "retry_send",
+    "cancel_send",
+    "retry_all_failed",
+    "get_edit_history",
+    "get_event_receipts_and_reactions",
+    "forward_event",
+    "format_markdown",
+    "execute_composer_input",
+    "autocomplete",
+    "get_room_power_levels",
+    "set_user_power_level",
+    "set_power_level_threshold",
+    "get_role_presets",
+    "save_role_preset",
+    "delete_role_preset",
+    "get_room_settings",
+    "update_room_settings",
+    "enable_room_encryption",
+    "get_supported_room_versions",
+    "upgrade_room",
+    "follow_tombstone",
+    "get_room_predecessor",
+    "create_room",
+    "get_or_create_group_dm",
+    "merge_duplicate_direct_entries",
+    "accept_invite",
+    "decline_invite",
+    "decline_invite_and_ignore_inviter",
+    "decline_invites_from_server",
+    "get_invite_policy",
+    "set_invite_policy",
+    "get_ignored_users",
+    "ignore_user",
+    "unignore_user",
+    "report_event",
+    "report_room",
+    "report_user",
+    "set_own_presence",
+    "report_user_activity",
+    "set_idle_timeout",
+    "change_password",
+    "get_third_party_ids",
+    "request_third_party_token",
+    "add_third_party_id",
+    "delete_third_party_id",
+    "deactivate_account",
+    "continue_uia",
+    "cancel_uia",
+    "get_uia_fallback_url",
+    "rename_device",
+    "delete_devices",
+    "sign_out_other_devices",
+    "get_stale_devices",
+    "get_registration_flows",
+    "check_username_availability",
+    "check_registration_token",
+    "request_registration_email_token",
+    "register_account",
+    "grant_qr_login",
+    "submit_qr_login_check_code",
+    "request_user_verification",
+    "accept_user_verification",
+    "start_sas_verification",
+    "get_user_verification_qr_code",
+    "scan_user_verification_qr_code",
+    "confirm_user_verification",
+    "cancel_user_verification",
 ];
 
 fn main() {
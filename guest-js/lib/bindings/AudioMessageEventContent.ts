@@ -1,7 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { AudioInfo } from './AudioInfo.js';
 import type { EncryptedFile } from './EncryptedFile.js';
-import type { FormattedBody } from './FormattedBody.js';
+import type { FormattedBody, SanitizedFormattedBody } from './FormattedBody.js';
 import type { UnstableAudioDetailsContentBlock } from './UnstableAudioDetailsContentBlock.js';
 import type { UnstableVoiceContentBlock } from './UnstableVoiceContentBlock.js';
 
@@ -42,4 +42,5 @@ export type AudioMessageEventContent = {
 	 */
 	'org.matrix.msc3245.voice': UnstableVoiceContentBlock | null;
 } & ({ url: string } | { file: EncryptedFile }) &
-	Partial<FormattedBody>;
+	Partial<FormattedBody> &
+	SanitizedFormattedBody;
@@ -1,6 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-import type { FormattedBody } from './FormattedBody.js';
+import type { FormattedBody, SanitizedFormattedBody } from './FormattedBody.js';
 
 /**
  * The payload for an emote message.
@@ -14,4 +14,5 @@ export type EmoteMessageEventContent = {
 	 * The formatted body. TODO: support formatted bodies
 	 */
 	// formatted: FormattedBody;
-} & Partial<FormattedBody>;
+} & Partial<FormattedBody> &
+	SanitizedFormattedBody;
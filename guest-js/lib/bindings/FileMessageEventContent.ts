@@ -1,7 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { EncryptedFile } from './EncryptedFile.js';
 import type { FileInfo } from './FileInfo.js';
-import type { FormattedBody } from './FormattedBody.js';
+import type { FormattedBody, SanitizedFormattedBody } from './FormattedBody.js';
 
 /**
  * The payload for a file message.
@@ -26,4 +26,5 @@ export type FileMessageEventContent = {
 	 */
 	info: FileInfo | null;
 } & ({ url: string } | { file: EncryptedFile }) &
-	Partial<FormattedBody>;
+	Partial<FormattedBody> &
+	SanitizedFormattedBody;
@@ -15,3 +15,13 @@ export type FormattedBody = {
 	 */
 	formatted_body: string;
 };
+
+// Manually typed
+/**
+ * Added by the plugin to incoming `org.matrix.custom.html` bodies. This is the HTML
+ * restricted to the tags and attributes allowed by the spec, without the reply fallback,
+ * and with images pointing to the `mxc` media scheme. Render this one, not `formatted_body`.
+ */
+export type SanitizedFormattedBody = {
+	sanitized_formatted_body?: string;
+};
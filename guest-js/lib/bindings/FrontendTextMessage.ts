@@ -1,6 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-import type { FormattedBody } from './FormattedBody.js';
+import type { FormattedBody, SanitizedFormattedBody } from './FormattedBody.js';
 
 /**
  * Custom TextMessage struct that adds a Vec of matched URLs
@@ -15,4 +15,5 @@ export type FrontendTextMessage = {
 	 * Custom utility field since we don't support full url_previews right now
 	 */
 	matched_urls: string[] | null;
-} & Partial<FormattedBody>;
+} & Partial<FormattedBody> &
+	SanitizedFormattedBody;
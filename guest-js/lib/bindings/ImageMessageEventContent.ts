@@ -1,6 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { EncryptedFile } from './EncryptedFile.js';
-import type { FormattedBody } from './FormattedBody.js';
+import type { FormattedBody, SanitizedFormattedBody } from './FormattedBody.js';
 import type { ImageInfo } from './ImageInfo.js';
 
 /**
@@ -26,4 +26,5 @@ export type ImageMessageEventContent = {
 	 */
 	info: ImageInfo | null;
 } & ({ url: string } | { file: EncryptedFile }) &
-	Partial<FormattedBody>;
+	Partial<FormattedBody> &
+	SanitizedFormattedBody;
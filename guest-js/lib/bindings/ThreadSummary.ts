@@ -4,6 +4,10 @@
  * The mapped thread summary containing the latest item in this thread + additionnal infos.
  */
 export type ThreadSummary = {
+	// Manually typed
+	/**
+	 * Sanitized by the plugin, like the `sanitized_formatted_body` of messages.
+	 */
 	eventFormattedSummary: string;
 	senderId: string;
 	numReplies: number;
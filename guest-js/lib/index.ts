@@ -15,10 +15,17 @@ import {
 import { LoginStore } from './stores/login-store.svelte.js';
 import { RoomStore } from './stores/room-store.svelte.js';
 import { RoomsCollection } from './stores/rooms-collection.svelte.js';
+import {
+	ScheduledMessagesStore,
+	type ScheduledMessage
+} from './stores/scheduled-messages.svelte.js';
 import type { MediaRequestParameters, SendMediaMessageRequest } from './matrix-requests/media.js';
 import type { TimelineItem } from './bindings/TimelineItem.js';
 import type { RoomPreview } from './bindings/RoomPreview.js';
 import type { MatrixUriPillInfo } from './bindings/MatrixUriPillInfo.js';
+import type { FormattedBody } from './bindings/FormattedBody.js';
+import type { Mentions } from './bindings/Mentions.js';
+import type { PresenceState } from './stores/room-store.svelte.js';
 
 export function submitMatrixLoginRequest(request: MatrixLoginPayload): Promise<null> {
 	return invoke('plugin:matrix-svelte|submit_matrix_login_request', {
@@ -38,6 +45,10 @@ export function checkHomeserverAuthType(): Promise<AuthTypeResponse> {
 	return invoke<AuthTypeResponse>('plugin:matrix-svelte|check_homeserver_auth_type', {});
 }
 
+/**
+ * Forward the OAuth redirect received through a deeplink. Not needed on desktop when
+ * `oauth_redirect_uri` is a loopback URI, the plugin captures the redirect itself.
+ */
 export function forwardOAuthLoginDeeplink(url: string): Promise<null> {
 	return invoke('plugin:matrix-svelte|forward_oauth_login_deeplink', { url });
 }
@@ -152,10 +163,11 @@ export function hasSessionStored(): Promise<boolean> {
 /**
  *
  * Give the ability to the user to reset its identity, losing all its historic
- * messages in the process.
+ * messages in the process. On OAuth homeservers, the approval URL is emitted through
+ * the `ResetCrossSigningUrl` event, otherwise the authentication continues with `continueUia`.
  */
-export function resetCrossSigning(password: string | null): Promise<null> {
-	return invoke('plugin:matrix-svelte|reset_cross_signing', { password });
+export function resetCrossSigning(): Promise<UiaOutcome<null>> {
+	return invoke<UiaOutcome<null>>('plugin:matrix-svelte|reset_cross_signing', {});
 }
 
 /**
@@ -308,7 +320,1211 @@ export function registerNotifications(token: string, userLanguage: string): Prom
 	return invoke('plugin:matrix-svelte|register_notifications', { token, userLanguage });
 }
 
-export { LoginStore, RoomStore, RoomsCollection, createMatrixRequest };
+/**
+ * Schedule a text message to be sent later.
+ * @param sendAt milliseconds since the Unix epoch
+ * @returns the id of the scheduled message
+ */
+export function scheduleTextMessage(
+	roomId: RoomId,
+	threadRoot: EventId | null,
+	inReplyTo: EventId | null,
+	body: string,
+	sendAt: number
+): Promise<string> {
+	return invoke<string>('plugin:matrix-svelte|schedule_text_message', {
+		roomId,
+		threadRoot,
+		inReplyTo,
+		body,
+		sendAt
+	});
+}
+
+/**
+ * Schedule a media message to be sent later.
+ * @param sendAt milliseconds since the Unix epoch
+ * @returns the id of the scheduled message
+ */
+export function scheduleMediaMessage(
+	request: SendMediaMessageRequest,
+	sendAt: number
+): Promise<string> {
+	return invoke<string>('plugin:matrix-svelte|schedule_media_message', {
+		...request,
+		sendAt
+	});
+}
+
+/**
+ * Get the scheduled messages queue. It is also mirrored in the `ScheduledMessagesStore`.
+ */
+export function getScheduledMessages(): Promise<ScheduledMessage[]> {
+	return invoke<ScheduledMessage[]>('plugin:matrix-svelte|get_scheduled_messages', {});
+}
+
+/**
+ * Edit the send time and/or the body (or caption for media) of a scheduled message.
+ * Fields that are "null" are just ignored (no-op). A failed message is scheduled again.
+ */
+export function editScheduledMessage(
+	id: string,
+	sendAt: number | null,
+	body: string | null
+): Promise<null> {
+	return invoke('plugin:matrix-svelte|edit_scheduled_message', { id, sendAt, body });
+}
+
+/**
+ * Remove a message from the scheduled messages queue.
+ */
+export function cancelScheduledMessage(id: string): Promise<null> {
+	return invoke('plugin:matrix-svelte|cancel_scheduled_message', { id });
+}
+
+/**
+ * Retry sending a failed local echo of the send queue.
+ */
+export function retrySend(roomId: RoomId, transactionId: string): Promise<null> {
+	return invoke('plugin:matrix-svelte|retry_send', { roomId, transactionId });
+}
+
+/**
+ * Cancel a local echo of the send queue that hasn't been sent yet.
+ */
+export function cancelSend(roomId: RoomId, transactionId: string): Promise<null> {
+	return invoke('plugin:matrix-svelte|cancel_send', { roomId, transactionId });
+}
+
+/**
+ * Retry sending all the failed local echoes, in every room.
+ */
+export function retryAllFailed(): Promise<null> {
+	return invoke('plugin:matrix-svelte|retry_all_failed', {});
+}
+
+/**
+ * A version of an edited message.
+ */
+export type MessageRevision = {
+	/**
+	 * The id of the original event, or of the `m.replace` event for edits.
+	 */
+	eventId: EventId;
+	timestamp: number;
+	body: string;
+	formattedBody: string | null;
+	/**
+	 * The HTML body restricted to what the spec allows, to use with `{@html}`.
+	 */
+	sanitizedFormattedBody: string | null;
+	isOriginal: boolean;
+};
+
+/**
+ * Get all the revisions of an edited message, from the original one to the latest edit.
+ */
+export function getEditHistory(roomId: RoomId, eventId: EventId): Promise<MessageRevision[]> {
+	return invoke<MessageRevision[]>('plugin:matrix-svelte|get_edit_history', { roomId, eventId });
+}
+
+/**
+ * A room member resolved from the room member list, along with
+ * the time of its receipt or reaction.
+ */
+export type ResolvedMember = {
+	userId: UserId;
+	displayName: string | null;
+	avatar: MxcUri | null;
+	timestamp: number | null;
+};
+
+export type EventReceiptsAndReactions = {
+	/**
+	 * Members whose read receipt points to this event, latest first.
+	 */
+	readers: ResolvedMember[];
+	/**
+	 * Senders of each reaction, grouped by key.
+	 */
+	reactions: Record<string, ResolvedMember[]>;
+};
+
+/**
+ * Get who has read an event and who reacted to it, with their display name and avatar.
+ */
+export function getEventReceiptsAndReactions(
+	roomId: RoomId,
+	eventId: EventId
+): Promise<EventReceiptsAndReactions> {
+	return invoke<EventReceiptsAndReactions>(
+		'plugin:matrix-svelte|get_event_receipts_and_reactions',
+		{ roomId, eventId }
+	);
+}
+
+/**
+ * The result of forwarding an event to one room.
+ * `error` is null if the event has been forwarded successfully.
+ */
+export type ForwardResult = {
+	roomId: RoomId;
+	error: string | null;
+};
+
+/**
+ * Forward a message to one or more rooms. Replies and thread relations are stripped,
+ * and encrypted attachments are re-uploaded for each target room.
+ * @returns the result for each target room
+ */
+export function forwardEvent(
+	sourceRoomId: RoomId,
+	eventId: EventId,
+	targetRoomIds: RoomId[]
+): Promise<ForwardResult[]> {
+	return invoke<ForwardResult[]>('plugin:matrix-svelte|forward_event', {
+		sourceRoomId,
+		eventId,
+		targetRoomIds
+	});
+}
+
+/**
+ * A composer message, ready to be used in a `m.room.message` content.
+ */
+export type FormattedMessage = {
+	/**
+	 * The plain text fallback, which is the Markdown as typed.
+	 */
+	body: string;
+	/**
+	 * Null if the message doesn't contain any formatting.
+	 */
+	formatted: FormattedBody | null;
+	mentions: Mentions;
+};
+
+/**
+ * Convert the Markdown typed in the composer to `org.matrix.custom.html`, following the
+ * tags allowed by the spec. `@user:server` and `#alias:server` become pills, `@room` is a
+ * room mention, and `||text||` is a spoiler.
+ * @param roomId if given, user pills are labelled with the members display names
+ */
+export function formatMarkdown(roomId: RoomId | null, text: string): Promise<FormattedMessage> {
+	return invoke<FormattedMessage>('plugin:matrix-svelte|format_markdown', { roomId, text });
+}
+
+/**
+ * What happened to the text typed in the composer.
+ */
+export type ComposerInputOutcome =
+	| { kind: 'sent' }
+	| { kind: 'executed'; command: string }
+	/** The command runs in the background, its result isn't known yet. */
+	| { kind: 'queued'; command: string }
+	| { kind: 'unknownCommand'; command: string }
+	| { kind: 'invalidArguments'; command: string; usage: string }
+	/** `command` is `null` when the input was to be sent as a text message. */
+	| { kind: 'missingPermission'; command: string | null };
+
+/**
+ * Execute the slash command typed in the composer (`/me`, `/shrug`, `/join`, `/leave`,
+ * `/invite`, `/kick`, `/ban`, `/unban`, `/nick`, `/myroomnick`, `/topic`, `/roomname`),
+ * or send the input as a text message if it isn't one. Start the input with `//`
+ * to send a message starting with `/`.
+ */
+export function executeComposerInput(
+	roomId: RoomId,
+	text: string,
+	threadRoot: EventId | null = null,
+	inReplyTo: EventId | null = null
+): Promise<ComposerInputOutcome> {
+	return invoke<ComposerInputOutcome>('plugin:matrix-svelte|execute_composer_input', {
+		roomId,
+		text,
+		threadRoot,
+		inReplyTo
+	});
+}
+
+export type AutocompleteTrigger = '@' | '#' | ':';
+
+/**
+ * A composer autocomplete suggestion. `completion` is the text to insert:
+ * the user id, the room alias (or id), the emoji, or `@room`.
+ */
+export type Suggestion = {
+	completion: string;
+	label: string;
+	avatar: MxcUri | null;
+} & (
+	| { kind: 'user'; userId: UserId }
+	| { kind: 'roomMention' }
+	| { kind: 'room'; roomId: RoomId }
+	| { kind: 'emoji' }
+);
+
+/**
+ * Get suggestions for the word being typed in the composer, best first.
+ * - `@`: room members ranked by match quality and recent activity, then the user directory.
+ *   `@room` is suggested if the user is allowed to notify the whole room.
+ * - `#`: joined rooms, matched on their name and aliases.
+ * - `:`: emoji shortcodes.
+ * @param prefix the typed word, without the trigger character
+ */
+export function autocomplete(
+	roomId: RoomId,
+	trigger: AutocompleteTrigger,
+	prefix: string,
+	limit: number
+): Promise<Suggestion[]> {
+	return invoke<Suggestion[]>('plugin:matrix-svelte|autocomplete', {
+		roomId,
+		trigger,
+		prefix,
+		limit
+	});
+}
+
+/**
+ * The `m.room.power_levels` content, as defined in the spec.
+ */
+export type RoomPowerLevelsContent = {
+	ban?: number;
+	events?: Record<string, number>;
+	events_default?: number;
+	invite?: number;
+	kick?: number;
+	notifications?: { room?: number };
+	redact?: number;
+	state_default?: number;
+	users?: Record<UserId, number>;
+	users_default?: number;
+};
+
+export type RoomPowerLevelsInfo = {
+	content: RoomPowerLevelsContent;
+	/**
+	 * Null for room creators, who have an infinite power level since room version 12.
+	 */
+	ownLevel: number | null;
+	canEdit: boolean;
+};
+
+export type PowerLevelThreshold =
+	| { kind: 'ban' }
+	| { kind: 'kick' }
+	| { kind: 'invite' }
+	| { kind: 'redact' }
+	| { kind: 'eventsDefault' }
+	| { kind: 'stateDefault' }
+	| { kind: 'usersDefault' }
+	| { kind: 'notifyRoom' }
+	| { kind: 'event'; eventType: string };
+
+/**
+ * A named power level. Built-in presets (admin and moderator) can't be changed.
+ */
+export type RolePreset = {
+	name: string;
+	level: number;
+	builtIn: boolean;
+};
+
+/**
+ * Get the full power levels of a room, and the level of the current user.
+ */
+export function getRoomPowerLevels(roomId: RoomId): Promise<RoomPowerLevelsInfo> {
+	return invoke<RoomPowerLevelsInfo>('plugin:matrix-svelte|get_room_power_levels', { roomId });
+}
+
+/**
+ * Set the power level of a user. Fails if the user is at or above the current user's level,
+ * if the level is higher than the current user's level, or if this would demote the last admin.
+ */
+export function setUserPowerLevel(roomId: RoomId, userId: UserId, level: number): Promise<null> {
+	return invoke('plugin:matrix-svelte|set_user_power_level', { roomId, userId, level });
+}
+
+/**
+ * Change a threshold of the power levels.
+ * @param level null to remove the level of an event type, which then falls back to the default
+ */
+export function setPowerLevelThreshold(
+	roomId: RoomId,
+	threshold: PowerLevelThreshold,
+	level: number | null
+): Promise<null> {
+	return invoke('plugin:matrix-svelte|set_power_level_threshold', { roomId, threshold, level });
+}
+
+export function getRolePresets(): Promise<RolePreset[]> {
+	return invoke<RolePreset[]>('plugin:matrix-svelte|get_role_presets', {});
+}
+
+/**
+ * Create or update a custom role preset.
+ */
+export function saveRolePreset(name: string, level: number): Promise<null> {
+	return invoke('plugin:matrix-svelte|save_role_preset', { name, level });
+}
+
+export function deleteRolePreset(name: string): Promise<null> {
+	return invoke('plugin:matrix-svelte|delete_role_preset', { name });
+}
+
+export type JoinRuleSetting =
+	| { kind: 'public' }
+	| { kind: 'invite' }
+	| { kind: 'knock' }
+	/**
+	 * Members of the given spaces can join without an invite.
+	 */
+	| { kind: 'restricted'; spaceIds: RoomId[] }
+	/**
+	 * Like `restricted`, but others can knock.
+	 */
+	| { kind: 'knockRestricted'; spaceIds: RoomId[] };
+
+export type HistoryVisibility = 'invited' | 'joined' | 'shared' | 'world_readable';
+
+export type GuestAccess = 'can_join' | 'forbidden';
+
+export type ServerAcl = {
+	allowIpLiterals: boolean;
+	allow: string[];
+	deny: string[];
+};
+
+export type RoomSettings = {
+	joinRule: JoinRuleSetting;
+	historyVisibility: HistoryVisibility;
+	guestAccess: GuestAccess;
+	isEncrypted: boolean;
+	/**
+	 * Null if the room doesn't have any server ACL.
+	 */
+	serverAcl: ServerAcl | null;
+	/**
+	 * Which settings the current user is allowed to change.
+	 */
+	canEdit: {
+		joinRule: boolean;
+		historyVisibility: boolean;
+		guestAccess: boolean;
+		encryption: boolean;
+		serverAcl: boolean;
+	};
+};
+
+/**
+ * The settings to change, the ones left to null are kept as is.
+ */
+export type RoomSettingsUpdate = {
+	joinRule: JoinRuleSetting | null;
+	historyVisibility: HistoryVisibility | null;
+	guestAccess: GuestAccess | null;
+	serverAcl: ServerAcl | null;
+};
+
+export function getRoomSettings(roomId: RoomId): Promise<RoomSettings> {
+	return invoke<RoomSettings>('plugin:matrix-svelte|get_room_settings', { roomId });
+}
+
+/**
+ * Change some settings of a room.
+ * @returns the new settings
+ */
+export function updateRoomSettings(
+	roomId: RoomId,
+	update: RoomSettingsUpdate
+): Promise<RoomSettings> {
+	return invoke<RoomSettings>('plugin:matrix-svelte|update_room_settings', { roomId, update });
+}
+
+/**
+ * Enable encryption in a room. This can't be undone, so `confirm` must be true.
+ */
+export function enableRoomEncryption(roomId: RoomId, confirm: boolean): Promise<null> {
+	return invoke('plugin:matrix-svelte|enable_room_encryption', { roomId, confirm });
+}
+
+export type RoomVersionInfo = {
+	version: string;
+	isStable: boolean;
+	isDefault: boolean;
+};
+
+export type RoomUpgradeResult = {
+	newRoomId: RoomId;
+	/**
+	 * Users with a pending invite in the old room that couldn't be invited in the new one.
+	 */
+	failedInvites: UserId[];
+};
+
+/**
+ * The room replaced by another one.
+ */
+export type RoomPredecessor = {
+	roomId: RoomId;
+	isJoined: boolean;
+	/**
+	 * The tombstone closing the predecessor history, to open its timeline where it ends.
+	 * Null if the predecessor isn't joined.
+	 */
+	tombstoneEventId: EventId | null;
+};
+
+/**
+ * Get the room versions supported by the homeserver.
+ */
+export function getSupportedRoomVersions(): Promise<RoomVersionInfo[]> {
+	return invoke<RoomVersionInfo[]>('plugin:matrix-svelte|get_supported_room_versions', {});
+}
+
+/**
+ * Upgrade a room to a new version. Users with a pending invite in the old room are invited
+ * in the new one.
+ */
+export function upgradeRoom(roomId: RoomId, newVersion: string): Promise<RoomUpgradeResult> {
+	return invoke<RoomUpgradeResult>('plugin:matrix-svelte|upgrade_room', { roomId, newVersion });
+}
+
+/**
+ * Join the room that replaced a tombstoned room (see `JoinedRoomInfo.isTombstoned`).
+ * @returns the id of the successor room
+ */
+export function followTombstone(roomId: RoomId): Promise<RoomId> {
+	return invoke<RoomId>('plugin:matrix-svelte|follow_tombstone', { roomId });
+}
+
+/**
+ * Get the room replaced by this room, if any, to link its history at the start of the timeline.
+ */
+export function getRoomPredecessor(roomId: RoomId): Promise<RoomPredecessor | null> {
+	return invoke<RoomPredecessor | null>('plugin:matrix-svelte|get_room_predecessor', { roomId });
+}
+
+export type RoomPresetOption = 'privateChat' | 'trustedPrivateChat' | 'publicChat';
+
+export type CreateRoomOptions = {
+	name: string | null;
+	/**
+	 * `trustedPrivateChat` gives invited users the same power level as the creator.
+	 */
+	preset: RoomPresetOption;
+	isEncrypted: boolean;
+	/**
+	 * The localpart of the main alias of the room, i.e. `foo` for `#foo:server`.
+	 */
+	aliasLocalpart: string | null;
+	/**
+	 * The homeserver default version is used if null (see `getSupportedRoomVersions`).
+	 */
+	roomVersion: string | null;
+	topic: string | null;
+	avatar: MxcUri | null;
+	invitedUserIds: UserId[];
+	/**
+	 * The space in which the room is added.
+	 */
+	parentSpaceId: RoomId | null;
+	historyVisibility: HistoryVisibility | null;
+	/**
+	 * Overrides of the power levels set by the preset.
+	 */
+	powerLevelOverrides: Partial<RoomPowerLevelsContent> | null;
+};
+
+export type CreatedRoom = {
+	roomId: RoomId;
+	/**
+	 * Why the room couldn't be added to the parent space. The room is created anyway.
+	 */
+	spaceError: string | null;
+};
+
+/**
+ * Create a room. Its id is also emitted through the `newly-created-room-id` event.
+ */
+export function createRoom(options: CreateRoomOptions): Promise<CreatedRoom> {
+	return invoke<CreatedRoom>('plugin:matrix-svelte|create_room', { options });
+}
+
+export type GroupDm = {
+	roomId: RoomId;
+	/**
+	 * Whether the room has just been created.
+	 */
+	isNew: boolean;
+};
+
+export type DirectCleanupResult = {
+	/**
+	 * The number of user entries merged into another one.
+	 */
+	mergedUsers: number;
+	/**
+	 * The number of duplicated room ids removed.
+	 */
+	removedRooms: number;
+	/**
+	 * The room to use with each user, which is moved first in their entry.
+	 */
+	canonicalRooms: Record<UserId, RoomId>;
+};
+
+/**
+ * Get the encrypted DM room with exactly the given users, or create it.
+ * A newly created room is also emitted through the `newly-created-room-id` event.
+ */
+export function getOrCreateGroupDm(userIds: UserId[]): Promise<GroupDm> {
+	return invoke<GroupDm>('plugin:matrix-svelte|get_or_create_group_dm', { userIds });
+}
+
+/**
+ * Merge the duplicated entries of the `m.direct` account data left behind by other clients,
+ * and list first the room to use with each user.
+ */
+export function mergeDuplicateDirectEntries(): Promise<DirectCleanupResult> {
+	return invoke<DirectCleanupResult>('plugin:matrix-svelte|merge_duplicate_direct_entries', {});
+}
+
+/**
+ * How incoming invites are handled before reaching the invites list.
+ */
+export type InvitePolicy = {
+	/**
+	 * Decline the invites from users who don't share any joined room with us.
+	 * The invites already pending when the app starts are kept.
+	 */
+	declineFromStrangers: boolean;
+};
+
+export function acceptInvite(roomId: RoomId): Promise<null> {
+	return invoke('plugin:matrix-svelte|accept_invite', { roomId });
+}
+
+/**
+ * Decline an invite, with an optional reason shown to the inviter.
+ */
+export function declineInvite(roomId: RoomId, reason: string | null = null): Promise<null> {
+	return invoke('plugin:matrix-svelte|decline_invite', { roomId, reason });
+}
+
+/**
+ * Decline an invite and ignore the inviter.
+ * @returns the ignored user, if the inviter is known
+ */
+export function declineInviteAndIgnoreInviter(
+	roomId: RoomId,
+	reason: string | null = null
+): Promise<UserId | null> {
+	return invoke<UserId | null>('plugin:matrix-svelte|decline_invite_and_ignore_inviter', {
+		roomId,
+		reason
+	});
+}
+
+/**
+ * Decline every pending invite sent by users of a homeserver.
+ * @returns the declined rooms
+ */
+export function declineInvitesFromServer(
+	serverName: string,
+	reason: string | null = null
+): Promise<RoomId[]> {
+	return invoke<RoomId[]>('plugin:matrix-svelte|decline_invites_from_server', {
+		serverName,
+		reason
+	});
+}
+
+export function getInvitePolicy(): Promise<InvitePolicy> {
+	return invoke<InvitePolicy>('plugin:matrix-svelte|get_invite_policy', {});
+}
+
+export function setInvitePolicy(policy: InvitePolicy): Promise<null> {
+	return invoke('plugin:matrix-svelte|set_invite_policy', { policy });
+}
+
+/**
+ * Get the users ignored by the current user.
+ */
+export function getIgnoredUsers(): Promise<UserId[]> {
+	return invoke<UserId[]>('plugin:matrix-svelte|get_ignored_users', {});
+}
+
+/**
+ * Ignore a user. Their messages and invites are hidden from the stores right away.
+ */
+export function ignoreUser(userId: UserId): Promise<null> {
+	return invoke('plugin:matrix-svelte|ignore_user', { userId });
+}
+
+export function unignoreUser(userId: UserId): Promise<null> {
+	return invoke('plugin:matrix-svelte|unignore_user', { userId });
+}
+
+/**
+ * What to do along with a report.
+ */
+export type ReportActions = {
+	/**
+	 * Redact the reported event, if allowed.
+	 */
+	redact?: boolean;
+	/**
+	 * Ignore the sender of the reported event, or the reported user.
+	 */
+	ignore?: boolean;
+};
+
+/**
+ * What happened to an action requested along with a report.
+ * `notAllowed` means we aren't allowed to redact this event in the room.
+ */
+export type ReportActionOutcome =
+	| { kind: 'notRequested' }
+	| { kind: 'done' }
+	| { kind: 'notAllowed' }
+	| { kind: 'failed'; error: string };
+
+/**
+ * The report itself succeeded, the actions may have not.
+ */
+export type ReportResult = {
+	redaction: ReportActionOutcome;
+	ignore: ReportActionOutcome;
+};
+
+/**
+ * An optional homeserver endpoint the app may have to work without.
+ */
+export type Endpoint = 'eventReports' | 'roomReports' | 'userReports';
+
+/**
+ * Commands reject with this instead of a plain message when the homeserver doesn't implement
+ * an endpoint.
+ */
+export type UnsupportedEndpointError = {
+	kind: 'unsupportedEndpoint';
+	endpoint: Endpoint;
+	message: string;
+};
+
+export function isUnsupportedEndpointError(error: unknown): error is UnsupportedEndpointError {
+	return (
+		typeof error === 'object' &&
+		error !== null &&
+		(error as { kind?: unknown }).kind === 'unsupportedEndpoint'
+	);
+}
+
+/**
+ * Report an event to the homeserver admins.
+ * Rejects with an `UnsupportedEndpointError` for `eventReports` if the endpoint isn't implemented.
+ * @param score from -100 (most offensive) to 0 (inoffensive)
+ */
+export function reportEvent(
+	roomId: RoomId,
+	eventId: EventId,
+	reason: string | null,
+	score: number | null = null,
+	actions: ReportActions | null = null
+): Promise<ReportResult> {
+	return invoke<ReportResult>('plugin:matrix-svelte|report_event', {
+		roomId,
+		eventId,
+		reason,
+		score,
+		actions
+	});
+}
+
+/**
+ * Report a room to the homeserver admins.
+ * Rejects with an `UnsupportedEndpointError` for `roomReports` if the endpoint isn't implemented.
+ */
+export function reportRoom(roomId: RoomId, reason: string): Promise<null> {
+	return invoke('plugin:matrix-svelte|report_room', { roomId, reason });
+}
+
+/**
+ * Report a user to the homeserver admins.
+ * Rejects with an `UnsupportedEndpointError` for `userReports` if the endpoint isn't implemented.
+ */
+export function reportUser(
+	userId: UserId,
+	reason: string,
+	actions: ReportActions | null = null
+): Promise<ReportResult> {
+	return invoke<ReportResult>('plugin:matrix-svelte|report_user', { userId, reason, actions });
+}
+
+/**
+ * Set our own presence and status message, mirrored in the login store.
+ * When `online` is chosen, the presence switches to `unavailable` after the idle timeout.
+ */
+export function setOwnPresence(
+	presence: PresenceState,
+	statusMsg: string | null = null
+): Promise<null> {
+	return invoke('plugin:matrix-svelte|set_own_presence', { presence, statusMsg });
+}
+
+/**
+ * To be called on user interactions (i.e. throttled pointer and key events),
+ * so the idle detector knows the user is active.
+ */
+export function reportUserActivity(): Promise<null> {
+	return invoke('plugin:matrix-svelte|report_user_activity', {});
+}
+
+/**
+ * Set the inactivity period after which our presence switches to `unavailable`.
+ * Defaults to 5 minutes. Rejects a zero timeout.
+ */
+export function setIdleTimeout(seconds: number): Promise<null> {
+	return invoke('plugin:matrix-svelte|set_idle_timeout', { seconds });
+}
+
+/**
+ * The outcome of an operation protected by user-interactive authentication.
+ * When `uiaRequired`, the operation continues with `continueUia`.
+ */
+export type UiaOutcome<T> =
+	| { kind: 'done'; result: T }
+	| {
+			kind: 'uiaRequired';
+			session: string;
+			/**
+			 * Each flow is a list of stage types, i.e. `m.login.password`.
+			 */
+			flows: string[][];
+			/**
+			 * The stages already completed in this session.
+			 */
+			completed: string[];
+			/**
+			 * The parameters of each stage, by stage type.
+			 */
+			params: Record<string, unknown>;
+			/**
+			 * The error of the last attempted stage, if any.
+			 */
+			error: string | null;
+	  };
+
+/**
+ * The auth object of a stage, as defined in the spec. The session is set by the plugin.
+ */
+export type UiaAuth = { type?: string } & Record<string, unknown>;
+
+export type ThirdPartyIdentifier = {
+	address: string;
+	medium: 'email' | 'msisdn';
+	validated_at: number;
+	added_at: number;
+};
+
+export type ThirdPartyAddress =
+	| { medium: 'email'; address: string }
+	| {
+			medium: 'msisdn';
+			/**
+			 * The two-letter uppercase ISO-3166-1 alpha-2 country code.
+			 */
+			country: string;
+			phoneNumber: string;
+	  };
+
+export type ThirdPartyValidation = {
+	sid: string;
+	clientSecret: string;
+	/**
+	 * Where the token received by SMS must be submitted (see `submitThirdPartyToken`).
+	 * Emails contain a link to validate the address instead.
+	 */
+	submitUrl: string | null;
+};
+
+/**
+ * Change the password, optionally logging out the other devices.
+ */
+export function changePassword(
+	newPassword: string,
+	logoutDevices: boolean
+): Promise<UiaOutcome<null>> {
+	return invoke<UiaOutcome<null>>('plugin:matrix-svelte|change_password', {
+		newPassword,
+		logoutDevices
+	});
+}
+
+export function getThirdPartyIds(): Promise<ThirdPartyIdentifier[]> {
+	return invoke<ThirdPartyIdentifier[]>('plugin:matrix-svelte|get_third_party_ids', {});
+}
+
+/**
+ * Ask the homeserver to send a validation token to a new email address or phone number.
+ * @param sendAttempt must be incremented to send the token again
+ */
+export function requestThirdPartyToken(
+	address: ThirdPartyAddress,
+	sendAttempt: number
+): Promise<ThirdPartyValidation> {
+	return invoke<ThirdPartyValidation>('plugin:matrix-svelte|request_third_party_token', {
+		address,
+		sendAttempt
+	});
+}
+
+/**
+ * Submit the token received by SMS to the `submitUrl` of a validation.
+ */
+export async function submitThirdPartyToken(
+	validation: ThirdPartyValidation,
+	token: string
+): Promise<void> {
+	if (!validation.submitUrl) {
+		throw new Error('The homeserver does not handle the validation of this token');
+	}
+	const response = await fetch(validation.submitUrl, {
+		method: 'POST',
+		headers: { 'Content-Type': 'application/json' },
+		body: JSON.stringify({
+			sid: validation.sid,
+			client_secret: validation.clientSecret,
+			token
+		})
+	});
+	if (!response.ok) {
+		throw new Error(`Token validation failed with status ${response.status}`);
+	}
+}
+
+/**
+ * Add an email address or phone number, once its token has been validated.
+ */
+export function addThirdPartyId(validation: ThirdPartyValidation): Promise<UiaOutcome<null>> {
+	return invoke<UiaOutcome<null>>('plugin:matrix-svelte|add_third_party_id', {
+		clientSecret: validation.clientSecret,
+		sid: validation.sid
+	});
+}
+
+export function deleteThirdPartyId(address: string, medium: 'email' | 'msisdn'): Promise<null> {
+	return invoke('plugin:matrix-svelte|delete_third_party_id', { address, medium });
+}
+
+/**
+ * Deactivate the account. With `erase`, the homeserver also forgets the sent messages
+ * as far as possible. On success, the user is signed out and the stored session is cleared.
+ */
+export function deactivateAccount(erase: boolean): Promise<UiaOutcome<null>> {
+	return invoke<UiaOutcome<null>>('plugin:matrix-svelte|deactivate_account', { erase });
+}
+
+/**
+ * Continue an operation that returned `uiaRequired` with the auth object of a stage, i.e.:
+ * - `{ type: 'm.login.password', password }` (the current user is used as identifier)
+ * - `{ type: 'm.login.recaptcha', response }`
+ * - `{ type: 'm.login.terms' }`
+ * - `{ type: 'm.login.email.identity', threepid_creds: { sid, client_secret } }`
+ * - `{}` once the page of `getUiaFallbackUrl` is completed (i.e. for `m.login.sso`)
+ *
+ * The session is set by the plugin.
+ */
+export function continueUia(session: string, auth: UiaAuth): Promise<UiaOutcome<unknown>> {
+	return invoke<UiaOutcome<unknown>>('plugin:matrix-svelte|continue_uia', { session, auth });
+}
+
+/**
+ * Forget an operation waiting for authentication.
+ */
+export function cancelUia(session: string): Promise<null> {
+	return invoke('plugin:matrix-svelte|cancel_uia', { session });
+}
+
+/**
+ * Get the URL of the web page completing a stage without native support (i.e. `m.login.sso`).
+ * Once the user is done, call `continueUia(session, {})`.
+ */
+export function getUiaFallbackUrl(stage: string, session: string): Promise<string> {
+	return invoke<string>('plugin:matrix-svelte|get_uia_fallback_url', { stage, session });
+}
+
+/**
+ * A device of the current user that hasn't been used for a long time.
+ */
+export type StaleDevice = {
+	deviceId: DeviceId;
+	displayName: string | null;
+	/**
+	 * Null if the homeserver never saw the device.
+	 */
+	lastSeenTs: number | null;
+	lastSeenIp: string | null;
+};
+
+export function renameDevice(deviceId: DeviceId, name: string): Promise<null> {
+	return invoke('plugin:matrix-svelte|rename_device', { deviceId, name });
+}
+
+/**
+ * Sign out devices of the current user. The current device can't be deleted this way.
+ */
+export function deleteDevices(deviceIds: DeviceId[]): Promise<UiaOutcome<null>> {
+	return invoke<UiaOutcome<null>>('plugin:matrix-svelte|delete_devices', { deviceIds });
+}
+
+/**
+ * Sign out every device of the current user but this one.
+ */
+export function signOutOtherDevices(): Promise<UiaOutcome<null>> {
+	return invoke<UiaOutcome<null>>('plugin:matrix-svelte|sign_out_other_devices', {});
+}
+
+/**
+ * Get the devices of the current user not seen for `thresholdDays` (90 by default), oldest first.
+ */
+export function getStaleDevices(thresholdDays: number | null = null): Promise<StaleDevice[]> {
+	return invoke<StaleDevice[]>('plugin:matrix-svelte|get_stale_devices', { thresholdDays });
+}
+
+export type RegistrationFlows = {
+	/**
+	 * The homeserver URL, resolved from the server name if needed.
+	 */
+	homeserverUrl: string;
+	/**
+	 * Each flow is a list of stage types, i.e. `m.login.registration_token`.
+	 */
+	flows: string[][];
+	/**
+	 * The parameters of each stage, by stage type, i.e. the policies of `m.login.terms`.
+	 */
+	params: Record<string, unknown>;
+};
+
+/**
+ * Discover the registration flows of a homeserver. It must be called before the other
+ * registration functions. Rejects if registration is disabled.
+ */
+export function getRegistrationFlows(homeserver: string): Promise<RegistrationFlows> {
+	return invoke<RegistrationFlows>('plugin:matrix-svelte|get_registration_flows', {
+		homeserver
+	});
+}
+
+/**
+ * Whether a username can be registered. Rejects if the username is invalid or reserved.
+ */
+export function checkUsernameAvailability(username: string): Promise<boolean> {
+	return invoke<boolean>('plugin:matrix-svelte|check_username_availability', { username });
+}
+
+/**
+ * Whether a token can be used for the `m.login.registration_token` stage.
+ */
+export function checkRegistrationToken(token: string): Promise<boolean> {
+	return invoke<boolean>('plugin:matrix-svelte|check_registration_token', { token });
+}
+
+/**
+ * Ask the homeserver to send a validation email for the `m.login.email.identity` stage.
+ * Once the link of the email is opened, continue with
+ * `{ type: 'm.login.email.identity', threepid_creds: { sid, client_secret } }`.
+ * @param sendAttempt must be incremented to send the email again
+ */
+export function requestRegistrationEmailToken(
+	email: string,
+	sendAttempt: number
+): Promise<ThirdPartyValidation> {
+	return invoke<ThirdPartyValidation>('plugin:matrix-svelte|request_registration_email_token', {
+		email,
+		sendAttempt
+	});
+}
+
+/**
+ * Register an account, then log in with it like with `submitMatrixLoginRequest`.
+ * The stages of the flows continue with `continueUia`, i.e.
+ * `{ type: 'm.login.registration_token', token }` or `{ type: 'm.login.dummy' }`.
+ * The result is the user id of the new account.
+ */
+export function registerAccount(
+	username: string,
+	password: string,
+	clientName: string
+): Promise<UiaOutcome<UserId>> {
+	return invoke<UiaOutcome<UserId>>('plugin:matrix-svelte|register_account', {
+		username,
+		password,
+		clientName
+	});
+}
+
+/**
+ * The progress of a login granted to a new device.
+ */
+export type QrLoginEvent =
+	| {
+			event: 'qrReady';
+			/**
+			 * The QR code to show to the new device, as base64. It can also be pasted as is.
+			 */
+			data: { qrCode: string };
+	  }
+	| {
+			/**
+			 * The code displayed by the new device must be submitted with `submitQrLoginCheckCode`.
+			 */
+			event: 'checkCodeRequested';
+	  }
+	| {
+			/**
+			 * The new device must be approved on this page of the homeserver.
+			 */
+			event: 'waitingForApproval';
+			data: { verificationUri: string };
+	  }
+	| { event: 'syncingSecrets' }
+	| { event: 'done' }
+	| { event: 'cancelled'; data: { reason: string } };
+
+/**
+ * Let a new device log in by scanning a QR code generated by this one (MSC4108).
+ * The homeserver must support OAuth. Emit `MatrixSvelteEmitEvent.CancelQrLogin` to cancel.
+ */
+export async function grantQrLogin(onEvent: Channel<QrLoginEvent>): Promise<null> {
+	return await invoke('plugin:matrix-svelte|grant_qr_login', { onEvent });
+}
+
+/**
+ * Submit the two-digit code displayed by the new device once it scanned the QR code.
+ */
+export function submitQrLoginCheckCode(code: number): Promise<null> {
+	return invoke('plugin:matrix-svelte|submit_qr_login_check_code', { code });
+}
+
+/**
+ * The progress of the verification of another user.
+ */
+export type UserVerificationEvent =
+	| {
+			/**
+			 * Waiting for the other user to accept the request.
+			 */
+			event: 'requested';
+			data: { flowId: string };
+	  }
+	| {
+			/**
+			 * Both users accepted. The verification continues with `startSasVerification`,
+			 * `getUserVerificationQrCode` or `scanUserVerificationQrCode`.
+			 */
+			event: 'ready';
+			data: { canShowQrCode: boolean; canScanQrCode: boolean };
+	  }
+	| {
+			/**
+			 * The emojis (if supported by both sides) and decimals to compare with the other user,
+			 * then to confirm with `confirmUserVerification`.
+			 */
+			event: 'sasKeys';
+			data: {
+				emojis: { symbol: string; description: string }[] | null;
+				decimals: [number, number, number];
+			};
+	  }
+	| {
+			/**
+			 * The other user scanned our QR code. Confirm it once they show it succeeded.
+			 */
+			event: 'qrCodeScanned';
+	  }
+	| { event: 'done' }
+	| { event: 'cancelled'; data: { reason: string } };
+
+/**
+ * Ask another user to verify their identity in the DM with them. Resolves once the
+ * verification is done or cancelled.
+ */
+export async function requestUserVerification(
+	onEvent: Channel<UserVerificationEvent>,
+	userId: UserId
+): Promise<null> {
+	return await invoke('plugin:matrix-svelte|request_user_verification', { onEvent, userId });
+}
+
+/**
+ * Accept a request received through `MatrixSvelteListenEvent.UserVerificationRequest`.
+ * Resolves once the verification is done or cancelled.
+ */
+export async function acceptUserVerification(
+	onEvent: Channel<UserVerificationEvent>,
+	userId: UserId,
+	flowId: string
+): Promise<null> {
+	return await invoke('plugin:matrix-svelte|accept_user_verification', {
+		onEvent,
+		userId,
+		flowId
+	});
+}
+
+/**
+ * Compare emojis or decimals with the other user.
+ */
+export function startSasVerification(userId: UserId, flowId: string): Promise<null> {
+	return invoke('plugin:matrix-svelte|start_sas_verification', { userId, flowId });
+}
+
+/**
+ * The QR code to show to the other user, as base64.
+ */
+export function getUserVerificationQrCode(userId: UserId, flowId: string): Promise<string> {
+	return invoke<string>('plugin:matrix-svelte|get_user_verification_qr_code', {
+		userId,
+		flowId
+	});
+}
+
+/**
+ * Scan the QR code shown by the other user, given as base64.
+ */
+export function scanUserVerificationQrCode(
+	userId: UserId,
+	flowId: string,
+	qrCode: string
+): Promise<null> {
+	return invoke('plugin:matrix-svelte|scan_user_verification_qr_code', {
+		userId,
+		flowId,
+		qrCode
+	});
+}
+
+/**
+ * Confirm that the emojis or decimals match, or that the other user scanned our QR code.
+ * The verification is cancelled if `matches` is false.
+ */
+export function confirmUserVerification(
+	userId: UserId,
+	flowId: string,
+	matches: boolean
+): Promise<null> {
+	return invoke('plugin:matrix-svelte|confirm_user_verification', { userId, flowId, matches });
+}
+
+export function cancelUserVerification(userId: UserId, flowId: string): Promise<null> {
+	return invoke('plugin:matrix-svelte|cancel_user_verification', { userId, flowId });
+}
+
+export { LoginStore, RoomStore, RoomsCollection, ScheduledMessagesStore, createMatrixRequest };
 
 export * from './bindings/AttachementInfo.js';
 export * from './bindings/AudioInfo.js';
@@ -384,3 +1600,19 @@ export * from './bindings/VirtualTimelineItem.js';
 export * from './tauri-events.js';
 export * from './type-guards.js';
 export * from './matrix-requests/media.js';
+export type {
+	MemberTrustLevel,
+	OutboxItem,
+	OutboxItemState,
+	PresenceState,
+	RoomMember,
+	RoomStoreType,
+	UserPresence
+} from './stores/room-store.svelte.js';
+export type { RoomsCollectionType } from './stores/rooms-collection.svelte.js';
+export type {
+	ScheduledMessage,
+	ScheduledMessageContent,
+	ScheduledMessageState,
+	ScheduledThumbnail
+} from './stores/scheduled-messages.svelte.js';
@@ -16,6 +16,8 @@ export type LoginStateType = {
 	userAvatar: string | null;
 	userDisplayName: string | null;
 	deviceDisplayName: string | null;
+	presence: 'online' | 'unavailable' | 'offline';
+	statusMessage: string | null;
 };
 
 export class LoginStore extends RuneStore<LoginStateType> {
@@ -42,7 +44,9 @@ export class LoginStore extends RuneStore<LoginStateType> {
 				deviceDisplayName: null,
 				recoveryState: 'unknown',
 				userAvatar: null,
-				userDisplayName: null
+				userDisplayName: null,
+				presence: 'online',
+				statusMessage: null
 			},
 			options
 		);
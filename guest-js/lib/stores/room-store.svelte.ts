@@ -1,9 +1,71 @@
 import { RuneStore, type StoreHooks } from '@tauri-store/svelte';
+import type { FrontendRoomMember } from '../bindings/FrontendRoomMember.js';
 import type { RoomScreen } from '../bindings/RoomScreen.js';
 
 export const ROOM_STORE_ID = 'room-store';
 
-export class RoomStore extends RuneStore<RoomScreen> {
+export type OutboxItemState =
+	| { state: 'pending' }
+	| { state: 'sending' }
+	| { state: 'failed'; reason: string; isRecoverable: boolean };
+
+/**
+ * A local echo that hasn't been acknowledged by the homeserver yet.
+ */
+export type OutboxItem = {
+	transactionId: string;
+	/**
+	 * Plain text body of the message, if any.
+	 */
+	body: string | null;
+	state: OutboxItemState;
+};
+
+export type PresenceState = 'online' | 'unavailable' | 'offline';
+
+/**
+ * The presence of another user.
+ */
+export type UserPresence = {
+	presence: PresenceState;
+	/**
+	 * When the user was last active, in milliseconds since the Unix epoch.
+	 */
+	lastActiveTs: number | null;
+	currentlyActive: boolean;
+	statusMsg: string | null;
+};
+
+/**
+ * Whether the identity of a user has been verified by the current user.
+ * `identityChanged` if the user was verified and their identity changed since.
+ */
+export type MemberTrustLevel = 'verified' | 'unverified' | 'identityChanged';
+
+export type RoomMember = FrontendRoomMember & {
+	/**
+	 * Missing until the identity of the member is known.
+	 */
+	trust?: MemberTrustLevel;
+};
+
+export type RoomStoreType = Omit<RoomScreen, 'members'> & {
+	/**
+	 * Known members of this room, by user id.
+	 */
+	members: Record<string, RoomMember>;
+	/**
+	 * The send queue of the currently opened room.
+	 */
+	sendQueue: OutboxItem[];
+	/**
+	 * The presence of the joined members of the currently opened room, by user id.
+	 * Only the members whose presence is known are present.
+	 */
+	memberPresence: Record<string, UserPresence>;
+};
+
+export class RoomStore extends RuneStore<RoomStoreType> {
 	/**
 	 * Creates a new RoomStore instance
 	 * @param id The Matrix room id used to name the store
@@ -11,14 +73,16 @@ export class RoomStore extends RuneStore<RoomScreen> {
 	 */
 	constructor() {
 		// Initialize with default empty Room state
-		const defaultRoom: RoomScreen = {
+		const defaultRoom: RoomStoreType = {
 			timelineKind: null,
 			roomName: 'Not known yet',
 			tlState: null,
 			allRoomsLoaded: false,
 			isLoaded: false,
 			pinnedEvents: [],
-			members: {}
+			members: {},
+			sendQueue: [],
+			memberPresence: {}
 		};
 
 		const hooks: StoreHooks = {
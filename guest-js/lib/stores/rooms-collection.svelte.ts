@@ -4,10 +4,18 @@ import {
 	type TauriPluginSvelteRuneStoreOptions
 } from '@tauri-store/svelte';
 import type { RoomsList } from '../bindings/RoomsList.js';
+import type { UserPresence } from './room-store.svelte.js';
 
 export const ROOMS_COLLECTION_STORE_ID = 'rooms-collection';
 
-export class RoomsCollection extends RuneStore<RoomsList> {
+export type RoomsCollectionType = RoomsList & {
+	/**
+	 * The presence of the users we have a DM with, by user id.
+	 */
+	dmPresence: Record<string, UserPresence>;
+};
+
+export class RoomsCollection extends RuneStore<RoomsCollectionType> {
 	/**
 	 * Creates a new RoomsCollections instance
 	 */
@@ -17,7 +25,7 @@ export class RoomsCollection extends RuneStore<RoomsList> {
 			// eslint-disable-next-line @typescript-eslint/no-explicit-any
 			error: (err: any) => console.error(err)
 		};
-		const options: TauriPluginSvelteRuneStoreOptions<RoomsList> = {
+		const options: TauriPluginSvelteRuneStoreOptions<RoomsCollectionType> = {
 			hooks,
 			save: false,
 			syncStrategy: 'debounce',
@@ -35,7 +43,8 @@ export class RoomsCollection extends RuneStore<RoomsList> {
 				status: { status: 'notLoaded', message: 'Initiating' },
 				currentActiveRoom: null,
 				maxKnownRooms: null,
-				filterKeywords: ''
+				filterKeywords: '',
+				dmPresence: {}
 			},
 			options
 		);
@@ -0,0 +1,73 @@
+import {
+	RuneStore,
+	type StoreHooks,
+	type TauriPluginSvelteRuneStoreOptions
+} from '@tauri-store/svelte';
+import type { AttachmentInfo } from '../bindings/AttachementInfo.js';
+import type { EventId, RoomId } from '../matrix-requests/common.js';
+
+export const SCHEDULED_MESSAGES_STORE_ID = 'scheduled-messages';
+
+/**
+ * The metadata of a scheduled thumbnail, its bytes are kept by the plugin.
+ */
+export type ScheduledThumbnail = {
+	contentType: string;
+	height: number;
+	width: number;
+	size: number;
+};
+
+export type ScheduledMessageContent =
+	| { kind: 'text'; body: string }
+	| {
+			kind: 'media';
+			filename: string;
+			mimeType: string;
+			caption: string | null;
+			info: AttachmentInfo;
+			thumbnail: ScheduledThumbnail | null;
+	  };
+
+export type ScheduledMessageState =
+	| { state: 'pending' }
+	| { state: 'sending' }
+	| { state: 'failed'; reason: string };
+
+export type ScheduledMessage = {
+	id: string;
+	roomId: RoomId;
+	threadRoot: EventId | null;
+	inReplyTo: EventId | null;
+	/**
+	 * Milliseconds since the Unix epoch
+	 */
+	sendAt: number;
+	content: ScheduledMessageContent;
+	state: ScheduledMessageState;
+};
+
+export type ScheduledMessagesType = {
+	/**
+	 * Scheduled messages, ordered by send time
+	 */
+	messages: ScheduledMessage[];
+};
+
+export class ScheduledMessagesStore extends RuneStore<ScheduledMessagesType> {
+	/**
+	 * Creates a new ScheduledMessagesStore instance
+	 */
+	constructor() {
+		const hooks: StoreHooks = {
+			// eslint-disable-next-line @typescript-eslint/no-explicit-any
+			error: (err: any) => console.error(err)
+		};
+		const options: TauriPluginSvelteRuneStoreOptions<ScheduledMessagesType> = {
+			hooks,
+			save: false
+		};
+
+		super(SCHEDULED_MESSAGES_STORE_ID, { messages: [] }, options);
+	}
+}
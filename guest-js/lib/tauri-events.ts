@@ -3,7 +3,8 @@
 export enum MatrixSvelteEmitEvent {
 	VerificationResult = 'matrix-svelte://verification-result',
 	CancelVerification = 'matrix-svelte://cancel-verification',
-	UpdateCurrentActiveRoom = 'matrix-svelte://update-current-active-room'
+	UpdateCurrentActiveRoom = 'matrix-svelte://update-current-active-room',
+	CancelQrLogin = 'matrix-svelte://cancel-qr-login'
 }
 
 export type VerificationResultEventType = {
@@ -24,9 +25,19 @@ export enum MatrixSvelteListenEvent {
 	OAuthUrl = 'matrix-svelte://oauth-url',
 	ResetCrossSigningUrl = 'matrix-svelte://reset-cross-signing-url',
 	NewlyCreatedRoomId = 'matrix-svelte://newly-created-room-id',
-	MatrixUriIntent = 'matrix-svelte://matrix-uri-intent'
+	MatrixUriIntent = 'matrix-svelte://matrix-uri-intent',
+	UserVerificationRequest = 'matrix-svelte://user-verification-request'
 }
 
+/**
+ * A verification request sent by another user in a DM, to answer with `acceptUserVerification`.
+ */
+export type UserVerificationRequestEventType = {
+	userId: string;
+	flowId: string;
+	roomId: string;
+};
+
 export type VerificationEmojisEventType = {
 	emojis: string;
 };
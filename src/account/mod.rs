@@ -0,0 +1,157 @@
+use matrix_sdk::ruma::api::client::account::change_password;
+use matrix_sdk::ruma::thirdparty::{Medium, ThirdPartyIdentifier};
+use matrix_sdk::ruma::{ClientSecret, OwnedClientSecret, OwnedSessionId, UInt};
+use matrix_ui_serializable::LoginState;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_svelte::ManagerExt;
+
+use crate::keyring::clear_session_in_keyring;
+use crate::state_updaters::LOGIN_STATE_STORE_ID;
+use crate::uia::{UiaOutcome, UiaSessions};
+use crate::utils::{get_app_dir_or_create_it, get_client};
+
+pub async fn change_password(
+    sessions: &UiaSessions,
+    new_password: String,
+    logout_devices: bool,
+) -> anyhow::Result<UiaOutcome> {
+    sessions
+        .start(move |auth| {
+            let mut request = change_password::v3::Request::new(new_password.clone());
+            request.logout_devices = logout_devices;
+            request.auth = auth;
+            async move {
+                get_client()?.send(request).await?;
+                Ok(())
+            }
+        })
+        .await
+}
+
+pub async fn get_third_party_ids() -> anyhow::Result<Vec<ThirdPartyIdentifier>> {
+    Ok(get_client()?.account().get_3pids().await?.threepids)
+}
+
+/// A validation token sent by the homeserver to a third-party identifier.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThirdPartyValidation {
+    pub sid: OwnedSessionId,
+    pub client_secret: OwnedClientSecret,
+    /// Where the token received by SMS must be submitted, if the homeserver handles it.
+    /// Emails contain a link to validate the address instead.
+    pub submit_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "medium")]
+pub enum ThirdPartyAddress {
+    Email {
+        address: String,
+    },
+    Msisdn {
+        /// The two-letter uppercase ISO-3166-1 alpha-2 country code.
+        country: String,
+        phone_number: String,
+    },
+}
+
+/// Ask the homeserver to send a validation token to a new third-party identifier.
+/// `send_attempt` must be incremented to send the token again.
+pub async fn request_third_party_token(
+    address: ThirdPartyAddress,
+    send_attempt: u64,
+) -> anyhow::Result<ThirdPartyValidation> {
+    let account = get_client()?.account();
+    let client_secret = ClientSecret::new();
+    let send_attempt = UInt::try_from(send_attempt)?;
+    let (sid, submit_url) = match address {
+        ThirdPartyAddress::Email { address } => {
+            let response = account
+                .request_3pid_email_token(&client_secret, &address, send_attempt)
+                .await?;
+            (response.sid, response.submit_url)
+        }
+        ThirdPartyAddress::Msisdn {
+            country,
+            phone_number,
+        } => {
+            let response = account
+                .request_3pid_msisdn_token(&client_secret, &country, &phone_number, send_attempt)
+                .await?;
+            (response.sid, response.submit_url)
+        }
+    };
+    Ok(ThirdPartyValidation {
+        sid,
+        client_secret,
+        submit_url,
+    })
+}
+
+/// Add a third-party identifier, once its validation token has been submitted.
+pub async fn add_third_party_id(
+    sessions: &UiaSessions,
+    client_secret: OwnedClientSecret,
+    sid: OwnedSessionId,
+) -> anyhow::Result<UiaOutcome> {
+    sessions
+        .start(move |auth| {
+            let client_secret = client_secret.clone();
+            let sid = sid.clone();
+            async move {
+                get_client()?
+                    .account()
+                    .add_3pid(&client_secret, &sid, auth)
+                    .await?;
+                Ok(())
+            }
+        })
+        .await
+}
+
+pub async fn delete_third_party_id(address: &str, medium: Medium) -> anyhow::Result<()> {
+    get_client()?
+        .account()
+        .delete_3pid(address, medium, None)
+        .await?;
+    Ok(())
+}
+
+/// Deactivate the account, then sign out like `disconnect_and_clear_session` does. With
+/// `erase`, the homeserver also forgets the messages sent by the account, as far as possible.
+pub async fn deactivate_account<R: Runtime>(
+    app_handle: AppHandle<R>,
+    sessions: &UiaSessions,
+    erase: bool,
+) -> anyhow::Result<UiaOutcome> {
+    sessions
+        .start(move |auth| {
+            let app_handle = app_handle.clone();
+            async move {
+                get_client()?
+                    .account()
+                    .deactivate(None, auth, erase)
+                    .await?;
+                sign_out(&app_handle).await
+            }
+        })
+        .await
+}
+
+/// The access token is invalidated by the deactivation, so the client and its sync must
+/// be stopped, and the login screen shown again.
+async fn sign_out<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<()> {
+    matrix_ui_serializable::commands::disconnect_user().await?;
+    clear_session_in_keyring(get_app_dir_or_create_it(app_handle)?)?;
+    app_handle.svelte().set(
+        LOGIN_STATE_STORE_ID,
+        "state",
+        LoginState::AwaitingForHomeserver.to_camel_case(),
+    )?;
+    app_handle
+        .svelte()
+        .set(LOGIN_STATE_STORE_ID, "userId", None::<String>)?;
+    Ok(())
+}
@@ -0,0 +1,409 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use matrix_sdk::ruma::{OwnedRoomId, RoomId};
+use matrix_ui_serializable::MatrixRequest;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::utils::get_client;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum AutocompleteTrigger {
+    #[serde(rename = "@")]
+    User,
+    #[serde(rename = "#")]
+    Room,
+    #[serde(rename = ":")]
+    Emoji,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Suggestion {
+    /// The text to insert in the composer.
+    pub completion: String,
+    pub label: String,
+    pub avatar: Option<String>,
+    #[serde(flatten)]
+    pub kind: SuggestionKind,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "kind"
+)]
+pub enum SuggestionKind {
+    User { user_id: String },
+    RoomMention,
+    Room { room_id: OwnedRoomId },
+    Emoji,
+}
+
+/// How well a candidate matches the typed prefix, the higher the better.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MatchQuality {
+    Substring,
+    WordStart,
+    Prefix,
+    Exact,
+}
+
+struct MemberSnapshot {
+    user_id: String,
+    name: String,
+    avatar: Option<String>,
+}
+
+#[derive(Default)]
+struct RoomSnapshot {
+    members: Vec<MemberSnapshot>,
+    can_notify_room: bool,
+    /// Senders of the loaded timeline items, most recent first.
+    recent_senders: Vec<String>,
+}
+
+/// The members and power of the current user in each room, as last sent to the room store.
+#[derive(Default)]
+pub struct AutocompleteCache {
+    rooms: Mutex<HashMap<OwnedRoomId, RoomSnapshot>>,
+}
+
+impl AutocompleteCache {
+    /// Keep a snapshot of a serialized `RoomScreen`.
+    pub fn update_from_room_screen(&self, room_screen: &Value) {
+        let Some(room_id) = room_screen
+            .pointer("/timelineKind/roomId")
+            .and_then(Value::as_str)
+            .and_then(|id| RoomId::parse(id).ok())
+        else {
+            return;
+        };
+
+        let members = room_screen
+            .get("members")
+            .and_then(Value::as_object)
+            .map(|members| {
+                members
+                    .iter()
+                    .filter(|(_, m)| m.get("membership").and_then(Value::as_str) == Some("join"))
+                    .map(|(user_id, m)| MemberSnapshot {
+                        user_id: user_id.clone(),
+                        name: m
+                            .get("name")
+                            .and_then(Value::as_str)
+                            .unwrap_or(user_id)
+                            .to_owned(),
+                        avatar: m
+                            .get("avatar")
+                            .and_then(Value::as_str)
+                            .map(ToOwned::to_owned),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let can_notify_room = room_screen
+            .pointer("/tlState/userPower")
+            .and_then(Value::as_array)
+            .is_some_and(|powers| powers.iter().any(|p| p == "notifyRoom"));
+
+        let mut recent_senders = Vec::new();
+        if let Some(items) = room_screen
+            .pointer("/tlState/items")
+            .and_then(Value::as_array)
+        {
+            for sender in items
+                .iter()
+                .rev()
+                .filter_map(|item| item.pointer("/data/senderId").and_then(Value::as_str))
+            {
+                if !recent_senders.iter().any(|s| s == sender) {
+                    recent_senders.push(sender.to_owned());
+                }
+            }
+        }
+
+        self.rooms.lock().expect("Failed to lock mutex").insert(
+            room_id,
+            RoomSnapshot {
+                members,
+                can_notify_room,
+                recent_senders,
+            },
+        );
+    }
+
+    /// Members of the room and `@room`, ranked by match quality then by recent activity.
+    fn user_suggestions(&self, room_id: &RoomId, prefix: &str) -> Vec<Suggestion> {
+        let rooms = self.rooms.lock().expect("Failed to lock mutex");
+        let Some(room) = rooms.get(room_id) else {
+            return Vec::new();
+        };
+
+        let mut candidates: Vec<(Suggestion, MatchQuality, usize)> = room
+            .members
+            .iter()
+            .filter_map(|member| {
+                let quality = match_quality(&member.name, prefix)
+                    .max(match_quality(&member.user_id[1..], prefix))?;
+                let activity = room
+                    .recent_senders
+                    .iter()
+                    .position(|s| *s == member.user_id)
+                    .unwrap_or(usize::MAX);
+                Some((
+                    user_suggestion(&member.user_id, &member.name, &member.avatar),
+                    quality,
+                    activity,
+                ))
+            })
+            .collect();
+
+        if room.can_notify_room
+            && let Some(quality) = match_quality("room", prefix)
+        {
+            candidates.push((
+                Suggestion {
+                    completion: "@room".to_owned(),
+                    label: "@room".to_owned(),
+                    avatar: None,
+                    kind: SuggestionKind::RoomMention,
+                },
+                quality,
+                usize::MAX,
+            ));
+        }
+
+        candidates.sort_by_key(|(s, quality, activity)| {
+            (Reverse(*quality), *activity, s.label.to_lowercase())
+        });
+        candidates.into_iter().map(|(s, _, _)| s).collect()
+    }
+}
+
+fn user_suggestion(user_id: &str, name: &str, avatar: &Option<String>) -> Suggestion {
+    Suggestion {
+        completion: user_id.to_owned(),
+        label: name.to_owned(),
+        avatar: avatar.clone(),
+        kind: SuggestionKind::User {
+            user_id: user_id.to_owned(),
+        },
+    }
+}
+
+fn match_quality(candidate: &str, prefix: &str) -> Option<MatchQuality> {
+    let candidate = candidate.to_lowercase();
+    let prefix = prefix.to_lowercase();
+    if candidate == prefix {
+        Some(MatchQuality::Exact)
+    } else if candidate.starts_with(&prefix) {
+        Some(MatchQuality::Prefix)
+    } else if candidate
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| word.starts_with(&prefix))
+    {
+        Some(MatchQuality::WordStart)
+    } else if candidate.contains(&prefix) {
+        Some(MatchQuality::Substring)
+    } else {
+        None
+    }
+}
+
+/// Suggestions for the word being typed after a trigger character, best first.
+pub async fn autocomplete(
+    cache: &AutocompleteCache,
+    room_id: &RoomId,
+    trigger: AutocompleteTrigger,
+    prefix: &str,
+    limit: usize,
+) -> anyhow::Result<Vec<Suggestion>> {
+    let mut suggestions = match trigger {
+        AutocompleteTrigger::User => {
+            let mut suggestions = cache.user_suggestions(room_id, prefix);
+            suggestions.truncate(limit);
+            if suggestions.len() < limit && !prefix.is_empty() {
+                let missing = limit - suggestions.len();
+                // The local results are still worth showing if the directory search fails.
+                let directory = search_user_directory(prefix, limit as u64)
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!("User directory search failed, only showing room members. {e}");
+                        Vec::new()
+                    });
+                let directory: Vec<Suggestion> = directory
+                    .into_iter()
+                    .filter(|d| !suggestions.iter().any(|s| s.completion == d.completion))
+                    .take(missing)
+                    .collect();
+                suggestions.extend(directory);
+            }
+            suggestions
+        }
+        AutocompleteTrigger::Room => room_suggestions(prefix)?,
+        AutocompleteTrigger::Emoji => emoji_suggestions(prefix),
+    };
+    suggestions.truncate(limit);
+    Ok(suggestions)
+}
+
+/// Fallback on the user directory, the same way as the `search_users` command.
+async fn search_user_directory(prefix: &str, limit: u64) -> anyhow::Result<Vec<Suggestion>> {
+    let (tx, rx) = matrix_ui_serializable::oneshot::channel();
+    matrix_ui_serializable::commands::submit_async_request(MatrixRequest::SearchUsers {
+        search_term: prefix.to_owned(),
+        limit,
+        content_sender: tx,
+    });
+    let profiles = rx
+        .await
+        .map_err(anyhow::Error::from)?
+        .map_err(anyhow::Error::from)?;
+
+    Ok(profiles
+        .into_iter()
+        .filter_map(|profile| {
+            let profile = serde_json::to_value(profile).ok()?;
+            let user_id = profile.get("userId")?.as_str()?;
+            let name = profile
+                .get("displayName")
+                .and_then(Value::as_str)
+                .unwrap_or(user_id);
+            let avatar = profile
+                .get("avatarUrl")
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned);
+            Some(user_suggestion(user_id, name, &avatar))
+        })
+        .collect())
+}
+
+fn room_suggestions(prefix: &str) -> anyhow::Result<Vec<Suggestion>> {
+    let mut candidates: Vec<(Suggestion, MatchQuality)> = get_client()?
+        .joined_rooms()
+        .into_iter()
+        .filter(|room| !room.is_space())
+        .filter_map(|room| {
+            let alias = room.canonical_alias();
+            let name = room
+                .name()
+                .or_else(|| alias.as_ref().map(ToString::to_string))
+                .unwrap_or_else(|| room.room_id().to_string());
+            let quality = std::iter::once(match_quality(&name, prefix))
+                .chain(
+                    alias
+                        .iter()
+                        .map(|a| match_quality(&a.as_str()[1..], prefix)),
+                )
+                .chain(
+                    room.alt_aliases()
+                        .iter()
+                        .map(|a| match_quality(&a.as_str()[1..], prefix)),
+                )
+                .flatten()
+                .max()?;
+            Some((
+                Suggestion {
+                    completion: alias
+                        .map(|a| a.to_string())
+                        .unwrap_or_else(|| room.room_id().to_string()),
+                    label: name,
+                    avatar: room.avatar_url().map(|a| a.to_string()),
+                    kind: SuggestionKind::Room {
+                        room_id: room.room_id().to_owned(),
+                    },
+                },
+                quality,
+            ))
+        })
+        .collect();
+    candidates.sort_by_key(|(s, quality)| (Reverse(*quality), s.label.to_lowercase()));
+    Ok(candidates.into_iter().map(|(s, _)| s).collect())
+}
+
+fn emoji_suggestions(prefix: &str) -> Vec<Suggestion> {
+    if prefix.is_empty() {
+        return Vec::new();
+    }
+    let mut candidates: Vec<(Suggestion, MatchQuality)> = emojis::iter()
+        .filter_map(|emoji| {
+            let (shortcode, quality) = emoji
+                .shortcodes()
+                .filter_map(|shortcode| Some((shortcode, match_quality(shortcode, prefix)?)))
+                .max_by_key(|(_, quality)| *quality)?;
+            Some((
+                Suggestion {
+                    completion: emoji.as_str().to_owned(),
+                    label: format!(":{shortcode}:"),
+                    avatar: None,
+                    kind: SuggestionKind::Emoji,
+                },
+                quality,
+            ))
+        })
+        .collect();
+    candidates.sort_by_key(|(s, quality)| (Reverse(*quality), s.label.len()));
+    candidates.into_iter().map(|(s, _)| s).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_matches_by_quality() {
+        assert!(MatchQuality::Exact > MatchQuality::Prefix);
+        assert!(MatchQuality::Prefix > MatchQuality::WordStart);
+        assert!(MatchQuality::WordStart > MatchQuality::Substring);
+    }
+
+    #[test]
+    fn matches_case_insensitively() {
+        assert_eq!(match_quality("Alice", "aLICE"), Some(MatchQuality::Exact));
+        assert_eq!(
+            match_quality("Alice Smith", "ALI"),
+            Some(MatchQuality::Prefix)
+        );
+    }
+
+    #[test]
+    fn matches_the_start_of_any_word() {
+        assert_eq!(
+            match_quality("Alice Smith", "smi"),
+            Some(MatchQuality::WordStart)
+        );
+        assert_eq!(
+            match_quality("@bob:example.org", "exa"),
+            Some(MatchQuality::WordStart)
+        );
+        assert_eq!(
+            match_quality("thumbs_up", "up"),
+            Some(MatchQuality::WordStart)
+        );
+    }
+
+    #[test]
+    fn falls_back_to_substrings() {
+        assert_eq!(match_quality("Alice", "lic"), Some(MatchQuality::Substring));
+        assert_eq!(match_quality("Alice", "bob"), None);
+    }
+
+    #[test]
+    fn an_empty_prefix_matches_everything() {
+        assert_eq!(match_quality("Alice", ""), Some(MatchQuality::Prefix));
+    }
+
+    #[test]
+    fn suggests_emojis_by_shortcode() {
+        assert!(emoji_suggestions("").is_empty());
+        let suggestions = emoji_suggestions("thumbsup");
+        assert_eq!(suggestions[0].completion, "👍");
+        assert_eq!(suggestions[0].label, ":thumbsup:");
+    }
+}
@@ -1,4 +1,8 @@
 use anyhow::anyhow;
+use matrix_sdk::ruma::OwnedTransactionId;
+use matrix_sdk::ruma::presence::PresenceState;
+use matrix_sdk::ruma::thirdparty::{Medium, ThirdPartyIdentifier};
+use matrix_sdk::ruma::{OwnedClientSecret, OwnedSessionId};
 use matrix_ui_serializable::commands::{
     MatrixUriPillInfo, OwnedEventId, OwnedServerName, SerializableRoomPreview, VerifyDeviceEvent,
 };
@@ -16,18 +20,43 @@ use matrix_ui_serializable::{
     UserProfile, get_timeline_kind, oneshot,
 };
 use mime_serde_shim::Wrapper as MimeWrapper;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::sync::mpsc::channel;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tauri::ipc::Channel;
-use tauri::{AppHandle, Listener, Runtime, command};
+use tauri::{AppHandle, Emitter, Listener, Manager, Runtime, command};
 use url::Url;
 
+use crate::account::{ThirdPartyAddress, ThirdPartyValidation};
+use crate::autocomplete::{AutocompleteCache, AutocompleteTrigger, Suggestion};
+use crate::create_room::{CreateRoomOptions, CreatedRoom};
+use crate::devices::StaleDevice;
+use crate::direct_messages::{DirectCleanupResult, GroupDm};
+use crate::edit_history::MessageRevision;
+use crate::formatting::FormattedMessage;
+use crate::forward::ForwardResult;
+use crate::invites::{InvitePolicy, InvitePolicyState};
 use crate::keyring::clear_session_in_keyring;
+use crate::outbox::Outbox;
+use crate::power_levels::{PowerLevelThreshold, RolePreset, RolePresets, RoomPowerLevelsInfo};
+use crate::presence::PresenceTracker;
+use crate::qr_login::{QrLoginEvent, QrLoginState};
+use crate::receipts::EventReceiptsAndReactions;
+use crate::registration::{RegistrationClient, RegistrationFlows};
+use crate::reporting::{ReportActions, ReportResult};
+use crate::room_settings::{RoomSettings, RoomSettingsUpdate};
+use crate::room_upgrade::{RoomPredecessor, RoomUpgradeResult, RoomVersionInfo};
+use crate::scheduled_messages::{
+    ScheduledMessage, ScheduledMessageContent, ScheduledMessageState, ScheduledMessagesQueue,
+    ScheduledThumbnail,
+};
+use crate::slash_commands::{ComposerContext, ComposerInputOutcome};
 use crate::state_updaters::Updaters;
-use crate::utils::{get_app_dir_or_create_it, get_plugin_config};
+use crate::uia::{UiaOutcome, UiaSessions};
+use crate::user_verification::UserVerificationEvent;
+use crate::utils::{get_app_dir_or_create_it, get_client, get_plugin_config};
 use crate::{AUTH_DEEPLINK_SENDER, Error};
 use crate::{LOGIN_SENDER, Result};
 
@@ -279,10 +308,14 @@ pub(crate) fn has_session_stored() -> bool {
 }
 
 #[command]
-pub(crate) async fn reset_cross_signing(password: Option<String>) -> Result<()> {
-    matrix_ui_serializable::commands::reset_cross_signing(password)
+/// Reset the cross-signing identity. Authentication continues with `continue_uia`.
+pub(crate) async fn reset_cross_signing<R: Runtime>(
+    app_handle: AppHandle<R>,
+) -> Result<UiaOutcome> {
+    let sessions = app_handle.state::<UiaSessions>();
+    crate::cross_signing::reset_cross_signing(&app_handle, &sessions)
         .await
-        .map_err(Error::MatrixLib)
+        .map_err(Into::into)
 }
 
 #[command]
@@ -402,7 +435,6 @@ pub(crate) async fn write_media_to_selected_folder<R: Runtime>(
     }
     #[cfg(not(target_os = "android"))]
     {
-        use tauri::Manager;
         use tauri_plugin_dialog::DialogExt;
 
         let selected_path = app_handle
@@ -461,8 +493,6 @@ pub(crate) async fn silent_save_matrix_media_to_cache_dir<R: Runtime>(
     }
     #[cfg(not(target_os = "android"))]
     {
-        use tauri::Manager;
-
         let path = app_handle.path().app_cache_dir()?.join(filename);
         std::fs::write(&path, &contents)?;
         Ok(path.to_string_lossy().to_string())
@@ -517,10 +547,12 @@ pub(crate) async fn android_share_matrix_media<R: Runtime>(
 pub(crate) async fn get_event_from_main_timeline(
     room_id: OwnedRoomId,
     event_id: OwnedEventId,
-) -> Result<FrontendTimelineItem> {
-    matrix_ui_serializable::commands::get_event_from_main_timeline(room_id, event_id)
-        .await
-        .map_err(Into::into)
+) -> Result<serde_json::Value> {
+    let item: FrontendTimelineItem =
+        matrix_ui_serializable::commands::get_event_from_main_timeline(room_id, event_id).await?;
+    let mut item = serde_json::to_value(item).map_err(anyhow::Error::from)?;
+    crate::formatting::sanitize::sanitize_timeline_item(&mut item);
+    Ok(item)
 }
 
 #[command(async)]
@@ -598,6 +630,817 @@ pub(crate) async fn get_matrix_to_permalink_for_room(room_id: OwnedRoomId) -> Re
         .map_err(Into::into)
 }
 
+#[command]
+/// Schedule a text message to be sent later and return its id.
+/// `send_at` is expressed in milliseconds since the Unix epoch.
+pub(crate) fn schedule_text_message<R: Runtime>(
+    app_handle: AppHandle<R>,
+    room_id: OwnedRoomId,
+    thread_root: Option<OwnedEventId>,
+    in_reply_to: Option<OwnedEventId>,
+    body: String,
+    send_at: u64,
+) -> Result<String> {
+    let message = ScheduledMessage {
+        id: String::new(),
+        room_id,
+        thread_root,
+        in_reply_to,
+        send_at,
+        content: ScheduledMessageContent::Text { body },
+        state: ScheduledMessageState::Pending,
+    };
+    app_handle
+        .state::<ScheduledMessagesQueue>()
+        .push(&app_handle, message, None, None)
+        .map_err(Into::into)
+}
+
+#[command(async)]
+#[allow(clippy::too_many_arguments)]
+/// Schedule a media message to be sent later, with the same arguments as `send_media_message`.
+pub(crate) async fn schedule_media_message<R: Runtime>(
+    app_handle: AppHandle<R>,
+    room_id: OwnedRoomId,
+    thread_root: Option<OwnedEventId>,
+    buffer: Vec<u8>,
+    filename: String,
+    mime_type: MimeWrapper,
+    caption: Option<String>,
+    in_reply_to: Option<OwnedEventId>,
+    info: AttachmentInfoDeserHelper,
+    thumbnail: Option<ThumbnailDeserHelper>,
+    send_at: u64,
+) -> Result<String> {
+    let (thumbnail, thumbnail_buffer) = thumbnail.map(ScheduledThumbnail::split).unzip();
+    let message = ScheduledMessage {
+        id: String::new(),
+        room_id,
+        thread_root,
+        in_reply_to,
+        send_at,
+        content: ScheduledMessageContent::Media {
+            filename,
+            mime_type,
+            caption,
+            info,
+            thumbnail,
+        },
+        state: ScheduledMessageState::Pending,
+    };
+    app_handle
+        .state::<ScheduledMessagesQueue>()
+        .push(&app_handle, message, Some(buffer), thumbnail_buffer)
+        .map_err(Into::into)
+}
+
+#[command]
+pub(crate) fn get_scheduled_messages<R: Runtime>(
+    app_handle: AppHandle<R>,
+) -> Vec<ScheduledMessage> {
+    app_handle.state::<ScheduledMessagesQueue>().list()
+}
+
+#[command]
+/// Edit the send time and/or the body (caption for media) of a scheduled message.
+/// Fields that are `None` are left untouched.
+pub(crate) fn edit_scheduled_message<R: Runtime>(
+    app_handle: AppHandle<R>,
+    id: String,
+    send_at: Option<u64>,
+    body: Option<String>,
+) -> Result<()> {
+    app_handle
+        .state::<ScheduledMessagesQueue>()
+        .edit(&app_handle, &id, send_at, body)
+        .map_err(Into::into)
+}
+
+#[command]
+pub(crate) fn cancel_scheduled_message<R: Runtime>(
+    app_handle: AppHandle<R>,
+    id: String,
+) -> Result<()> {
+    app_handle
+        .state::<ScheduledMessagesQueue>()
+        .cancel(&app_handle, &id)
+        .map_err(Into::into)
+}
+
+#[command]
+/// Retry sending a failed local echo.
+pub(crate) async fn retry_send<R: Runtime>(
+    app_handle: AppHandle<R>,
+    room_id: OwnedRoomId,
+    transaction_id: OwnedTransactionId,
+) -> Result<()> {
+    app_handle
+        .state::<Outbox>()
+        .retry_send(&room_id, &transaction_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[command]
+/// Cancel a local echo that hasn't been sent yet.
+pub(crate) async fn cancel_send<R: Runtime>(
+    app_handle: AppHandle<R>,
+    room_id: OwnedRoomId,
+    transaction_id: OwnedTransactionId,
+) -> Result<()> {
+    app_handle
+        .state::<Outbox>()
+        .cancel_send(&room_id, &transaction_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[command]
+pub(crate) async fn retry_all_failed<R: Runtime>(app_handle: AppHandle<R>) -> Result<()> {
+    app_handle
+        .state::<Outbox>()
+        .retry_all_failed()
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Get all the revisions of an edited message, in chronological order.
+pub(crate) async fn get_edit_history(
+    room_id: OwnedRoomId,
+    event_id: OwnedEventId,
+) -> Result<Vec<MessageRevision>> {
+    crate::edit_history::get_edit_history(&room_id, &event_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Get the members who have read an event, and the senders of each of its reactions.
+pub(crate) async fn get_event_receipts_and_reactions(
+    room_id: OwnedRoomId,
+    event_id: OwnedEventId,
+) -> Result<EventReceiptsAndReactions> {
+    crate::receipts::get_event_receipts_and_reactions(&room_id, &event_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Forward a message to one or more rooms. Replies and thread relations are stripped,
+/// and encrypted attachments are re-uploaded for each target room.
+pub(crate) async fn forward_event(
+    source_room_id: OwnedRoomId,
+    event_id: OwnedEventId,
+    target_room_ids: Vec<OwnedRoomId>,
+) -> Result<Vec<ForwardResult>> {
+    crate::forward::forward_event(&source_room_id, &event_id, target_room_ids)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Convert the Markdown typed in the composer to a formatted body and its mentions.
+/// If a room is given, user pills are labelled with the members display names.
+pub(crate) async fn format_markdown(
+    room_id: Option<OwnedRoomId>,
+    text: String,
+) -> Result<FormattedMessage> {
+    crate::formatting::format_markdown(room_id.as_deref(), &text)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Execute the slash command typed in the composer, or send the input as a text message
+/// if it isn't one.
+pub(crate) async fn execute_composer_input(
+    room_id: OwnedRoomId,
+    text: String,
+    thread_root: Option<OwnedEventId>,
+    in_reply_to: Option<OwnedEventId>,
+) -> Result<ComposerInputOutcome> {
+    let context = ComposerContext {
+        thread_root,
+        in_reply_to,
+    };
+    crate::slash_commands::execute_composer_input(&room_id, &text, context)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Suggestions for the word being typed in the composer after `@`, `#` or `:`.
+pub(crate) async fn autocomplete<R: Runtime>(
+    app_handle: AppHandle<R>,
+    room_id: OwnedRoomId,
+    trigger: AutocompleteTrigger,
+    prefix: String,
+    limit: usize,
+) -> Result<Vec<Suggestion>> {
+    let cache = app_handle.state::<AutocompleteCache>();
+    crate::autocomplete::autocomplete(&cache, &room_id, trigger, &prefix, limit)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Get the full `m.room.power_levels` content of a room, and the level of the current user.
+pub(crate) async fn get_room_power_levels(room_id: OwnedRoomId) -> Result<RoomPowerLevelsInfo> {
+    crate::power_levels::get_room_power_levels(&room_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Set the power level of a user. The current user can't change the level of someone
+/// at or above their own level, nor demote the last administrator.
+pub(crate) async fn set_user_power_level(
+    room_id: OwnedRoomId,
+    user_id: OwnedUserId,
+    level: i64,
+) -> Result<()> {
+    crate::power_levels::set_user_power_level(&room_id, &user_id, level)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Change a threshold of the power levels, i.e. the level required to ban or to send an event type.
+pub(crate) async fn set_power_level_threshold(
+    room_id: OwnedRoomId,
+    threshold: PowerLevelThreshold,
+    level: Option<i64>,
+) -> Result<()> {
+    crate::power_levels::set_power_level_threshold(&room_id, threshold, level)
+        .await
+        .map_err(Into::into)
+}
+
+#[command]
+pub(crate) fn get_role_presets<R: Runtime>(app_handle: AppHandle<R>) -> Vec<RolePreset> {
+    app_handle.state::<RolePresets>().get()
+}
+
+#[command]
+/// Create or update a custom role preset.
+pub(crate) fn save_role_preset<R: Runtime>(
+    app_handle: AppHandle<R>,
+    name: String,
+    level: i64,
+) -> Result<()> {
+    app_handle
+        .state::<RolePresets>()
+        .save(name, level)
+        .map_err(Into::into)
+}
+
+#[command]
+pub(crate) fn delete_role_preset<R: Runtime>(app_handle: AppHandle<R>, name: String) -> Result<()> {
+    app_handle
+        .state::<RolePresets>()
+        .delete(&name)
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Get the join rule, history visibility, guest access, encryption and server ACL of a room.
+pub(crate) async fn get_room_settings(room_id: OwnedRoomId) -> Result<RoomSettings> {
+    crate::room_settings::get_room_settings(&room_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Change some settings of a room, and get the new ones.
+pub(crate) async fn update_room_settings(
+    room_id: OwnedRoomId,
+    update: RoomSettingsUpdate,
+) -> Result<RoomSettings> {
+    crate::room_settings::update_room_settings(&room_id, update)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Enable encryption in a room. As this is irreversible, `confirm` must be true.
+pub(crate) async fn enable_room_encryption(room_id: OwnedRoomId, confirm: bool) -> Result<()> {
+    crate::room_settings::enable_room_encryption(&room_id, confirm)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Get the room versions supported by the homeserver.
+pub(crate) async fn get_supported_room_versions() -> Result<Vec<RoomVersionInfo>> {
+    crate::room_upgrade::get_supported_room_versions()
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Upgrade a room to a new version, and invite again the users that were invited in the old room.
+pub(crate) async fn upgrade_room(
+    room_id: OwnedRoomId,
+    new_version: String,
+) -> Result<RoomUpgradeResult> {
+    crate::room_upgrade::upgrade_room(&room_id, &new_version)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Join the room that replaced a tombstoned room, and return its id.
+pub(crate) async fn follow_tombstone(room_id: OwnedRoomId) -> Result<OwnedRoomId> {
+    crate::room_upgrade::follow_tombstone(&room_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Get the room replaced by this room, if any, to link its history at the start of the timeline.
+pub(crate) async fn get_room_predecessor(room_id: OwnedRoomId) -> Result<Option<RoomPredecessor>> {
+    crate::room_upgrade::get_room_predecessor(&room_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Create a room, emit its id through `matrix-svelte://newly-created-room-id` and return it
+/// along with any error adding it to its parent space.
+pub(crate) async fn create_room<R: Runtime>(
+    app_handle: AppHandle<R>,
+    options: CreateRoomOptions,
+) -> Result<CreatedRoom> {
+    let created = crate::create_room::create_room(options).await?;
+    app_handle.emit("matrix-svelte://newly-created-room-id", &created.room_id)?;
+    Ok(created)
+}
+
+#[command(async)]
+/// Get the encrypted DM room with exactly the given users, or create it.
+/// A newly created room id is also emitted through `matrix-svelte://newly-created-room-id`.
+pub(crate) async fn get_or_create_group_dm<R: Runtime>(
+    app_handle: AppHandle<R>,
+    user_ids: Vec<OwnedUserId>,
+) -> Result<GroupDm> {
+    let dm = crate::direct_messages::get_or_create_group_dm(user_ids).await?;
+    if dm.is_new {
+        app_handle.emit("matrix-svelte://newly-created-room-id", &dm.room_id)?;
+    }
+    Ok(dm)
+}
+
+#[command(async)]
+/// Merge the duplicated entries of the `m.direct` account data left behind by other clients.
+pub(crate) async fn merge_duplicate_direct_entries() -> Result<DirectCleanupResult> {
+    crate::direct_messages::merge_duplicate_direct_entries()
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+pub(crate) async fn accept_invite(room_id: OwnedRoomId) -> Result<()> {
+    crate::invites::accept_invite(&room_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Decline an invite, with an optional reason shown to the inviter.
+pub(crate) async fn decline_invite(room_id: OwnedRoomId, reason: Option<String>) -> Result<()> {
+    crate::invites::decline_invite(&room_id, reason)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Decline an invite and ignore the inviter. Returns the ignored user, if known.
+pub(crate) async fn decline_invite_and_ignore_inviter(
+    room_id: OwnedRoomId,
+    reason: Option<String>,
+) -> Result<Option<OwnedUserId>> {
+    crate::invites::decline_invite_and_ignore_inviter(&room_id, reason)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Decline every pending invite sent from a homeserver, and return the declined rooms.
+pub(crate) async fn decline_invites_from_server(
+    server_name: OwnedServerName,
+    reason: Option<String>,
+) -> Result<Vec<OwnedRoomId>> {
+    crate::invites::decline_invites_from_server(&server_name, reason)
+        .await
+        .map_err(Into::into)
+}
+
+#[command]
+pub(crate) fn get_invite_policy<R: Runtime>(app_handle: AppHandle<R>) -> InvitePolicy {
+    app_handle.state::<InvitePolicyState>().get()
+}
+
+#[command]
+pub(crate) fn set_invite_policy<R: Runtime>(
+    app_handle: AppHandle<R>,
+    policy: InvitePolicy,
+) -> Result<()> {
+    app_handle
+        .state::<InvitePolicyState>()
+        .set(policy)
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Get the users in the `m.ignored_user_list` account data.
+pub(crate) async fn get_ignored_users() -> Result<Vec<OwnedUserId>> {
+    crate::ignored_users::get_ignored_users()
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Ignore a user, and hide their messages and invites.
+pub(crate) async fn ignore_user<R: Runtime>(
+    app_handle: AppHandle<R>,
+    user_id: OwnedUserId,
+) -> Result<()> {
+    crate::ignored_users::ignore_user(&app_handle, &user_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+pub(crate) async fn unignore_user<R: Runtime>(
+    app_handle: AppHandle<R>,
+    user_id: OwnedUserId,
+) -> Result<()> {
+    crate::ignored_users::unignore_user(&app_handle, &user_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Report an event to the homeserver admins, and optionally redact it or ignore its sender.
+pub(crate) async fn report_event<R: Runtime>(
+    app_handle: AppHandle<R>,
+    room_id: OwnedRoomId,
+    event_id: OwnedEventId,
+    reason: Option<String>,
+    score: Option<i64>,
+    actions: Option<ReportActions>,
+) -> Result<ReportResult> {
+    crate::reporting::report_event(
+        &app_handle,
+        &room_id,
+        &event_id,
+        reason,
+        score,
+        actions.unwrap_or_default(),
+    )
+    .await
+}
+
+#[command(async)]
+/// Report a room to the homeserver admins.
+pub(crate) async fn report_room(room_id: OwnedRoomId, reason: String) -> Result<()> {
+    crate::reporting::report_room(&room_id, reason).await
+}
+
+#[command(async)]
+/// Report a user to the homeserver admins, and optionally ignore them.
+pub(crate) async fn report_user<R: Runtime>(
+    app_handle: AppHandle<R>,
+    user_id: OwnedUserId,
+    reason: String,
+    actions: Option<ReportActions>,
+) -> Result<ReportResult> {
+    crate::reporting::report_user(&app_handle, &user_id, reason, actions.unwrap_or_default()).await
+}
+
+#[command(async)]
+/// Set our own presence and status message.
+pub(crate) async fn set_own_presence<R: Runtime>(
+    app_handle: AppHandle<R>,
+    presence: PresenceState,
+    status_msg: Option<String>,
+) -> Result<()> {
+    crate::presence::set_own_presence(&app_handle, presence, status_msg)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// To be called on user interactions, so the idle detector knows the user is active.
+pub(crate) async fn report_user_activity<R: Runtime>(app_handle: AppHandle<R>) -> Result<()> {
+    crate::presence::report_user_activity(&app_handle)
+        .await
+        .map_err(Into::into)
+}
+
+#[command]
+/// Set the inactivity period after which our presence switches to `unavailable`.
+pub(crate) fn set_idle_timeout<R: Runtime>(app_handle: AppHandle<R>, seconds: u64) -> Result<()> {
+    app_handle
+        .state::<PresenceTracker>()
+        .set_idle_timeout(Duration::from_secs(seconds))
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Change the password, optionally logging out the other devices.
+pub(crate) async fn change_password<R: Runtime>(
+    app_handle: AppHandle<R>,
+    new_password: String,
+    logout_devices: bool,
+) -> Result<UiaOutcome> {
+    let sessions = app_handle.state::<UiaSessions>();
+    crate::account::change_password(&sessions, new_password, logout_devices)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Get the email addresses and phone numbers of the account.
+pub(crate) async fn get_third_party_ids() -> Result<Vec<ThirdPartyIdentifier>> {
+    crate::account::get_third_party_ids()
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Ask the homeserver to send a validation token to a new email address or phone number.
+pub(crate) async fn request_third_party_token(
+    address: ThirdPartyAddress,
+    send_attempt: u64,
+) -> Result<ThirdPartyValidation> {
+    crate::account::request_third_party_token(address, send_attempt)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Add an email address or phone number once validated.
+pub(crate) async fn add_third_party_id<R: Runtime>(
+    app_handle: AppHandle<R>,
+    client_secret: OwnedClientSecret,
+    sid: OwnedSessionId,
+) -> Result<UiaOutcome> {
+    let sessions = app_handle.state::<UiaSessions>();
+    crate::account::add_third_party_id(&sessions, client_secret, sid)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+pub(crate) async fn delete_third_party_id(address: String, medium: Medium) -> Result<()> {
+    crate::account::delete_third_party_id(&address, medium)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Deactivate the account, optionally erasing its data. The user is signed out on success.
+pub(crate) async fn deactivate_account<R: Runtime>(
+    app_handle: AppHandle<R>,
+    erase: bool,
+) -> Result<UiaOutcome> {
+    let sessions = app_handle.state::<UiaSessions>();
+    crate::account::deactivate_account(app_handle.clone(), &sessions, erase)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Continue an operation that requires user-interactive authentication with the auth
+/// object of a stage. The session is set by the plugin.
+pub(crate) async fn continue_uia<R: Runtime>(
+    app_handle: AppHandle<R>,
+    session: String,
+    auth: serde_json::Value,
+) -> Result<UiaOutcome> {
+    app_handle
+        .state::<UiaSessions>()
+        .continue_uia(&session, auth)
+        .await
+        .map_err(Into::into)
+}
+
+#[command]
+pub(crate) fn cancel_uia<R: Runtime>(app_handle: AppHandle<R>, session: String) {
+    app_handle.state::<UiaSessions>().cancel(&session);
+}
+
+#[command]
+/// Get the URL of the web page completing a stage the app doesn't support natively.
+pub(crate) fn get_uia_fallback_url<R: Runtime>(
+    app_handle: AppHandle<R>,
+    stage: String,
+    session: String,
+) -> Result<String> {
+    // Registration happens before the client is logged in.
+    let homeserver = match get_client() {
+        Ok(client) => client.homeserver(),
+        Err(_) => app_handle.state::<RegistrationClient>().homeserver()?,
+    };
+    crate::uia::get_fallback_url(&homeserver, &stage, &session).map_err(Into::into)
+}
+
+#[command(async)]
+pub(crate) async fn rename_device(device_id: OwnedDeviceId, name: String) -> Result<()> {
+    crate::devices::rename_device(&device_id, &name)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Sign out devices of the current user. Authentication continues with `continue_uia`.
+pub(crate) async fn delete_devices<R: Runtime>(
+    app_handle: AppHandle<R>,
+    device_ids: Vec<OwnedDeviceId>,
+) -> Result<UiaOutcome> {
+    let sessions = app_handle.state::<UiaSessions>();
+    crate::devices::delete_devices(&sessions, device_ids)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Sign out every device of the current user but this one.
+pub(crate) async fn sign_out_other_devices<R: Runtime>(
+    app_handle: AppHandle<R>,
+) -> Result<UiaOutcome> {
+    let sessions = app_handle.state::<UiaSessions>();
+    crate::devices::sign_out_other_devices(&sessions)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Get the devices of the current user not seen for `threshold_days` (90 by default).
+pub(crate) async fn get_stale_devices(threshold_days: Option<u64>) -> Result<Vec<StaleDevice>> {
+    crate::devices::get_stale_devices(
+        threshold_days.unwrap_or(crate::devices::DEFAULT_STALE_THRESHOLD_DAYS),
+    )
+    .await
+    .map_err(Into::into)
+}
+
+#[command(async)]
+/// Discover the registration flows of a homeserver, before registering on it.
+pub(crate) async fn get_registration_flows<R: Runtime>(
+    app_handle: AppHandle<R>,
+    homeserver: String,
+) -> Result<RegistrationFlows> {
+    let state = app_handle.state::<RegistrationClient>();
+    crate::registration::get_registration_flows(&state, &homeserver)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+pub(crate) async fn check_username_availability<R: Runtime>(
+    app_handle: AppHandle<R>,
+    username: String,
+) -> Result<bool> {
+    let state = app_handle.state::<RegistrationClient>();
+    crate::registration::check_username_availability(&state, &username)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+pub(crate) async fn check_registration_token<R: Runtime>(
+    app_handle: AppHandle<R>,
+    token: String,
+) -> Result<bool> {
+    let state = app_handle.state::<RegistrationClient>();
+    crate::registration::check_registration_token(&state, &token)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+pub(crate) async fn request_registration_email_token<R: Runtime>(
+    app_handle: AppHandle<R>,
+    email: String,
+    send_attempt: u64,
+) -> Result<ThirdPartyValidation> {
+    let state = app_handle.state::<RegistrationClient>();
+    crate::registration::request_registration_email_token(&state, email, send_attempt)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Register an account, then log in with it. Authentication continues with `continue_uia`.
+pub(crate) async fn register_account<R: Runtime>(
+    app_handle: AppHandle<R>,
+    username: String,
+    password: String,
+    client_name: String,
+) -> Result<UiaOutcome> {
+    let state = app_handle.state::<RegistrationClient>();
+    let sessions = app_handle.state::<UiaSessions>();
+    crate::registration::register(&state, &sessions, username, password, client_name)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Let a new device log in by scanning a QR code, progress is reported through `on_event`.
+pub(crate) async fn grant_qr_login<R: Runtime>(
+    app_handle: AppHandle<R>,
+    on_event: Channel<QrLoginEvent>,
+) -> Result<()> {
+    crate::qr_login::grant_qr_login(&app_handle, on_event)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+pub(crate) async fn submit_qr_login_check_code<R: Runtime>(
+    app_handle: AppHandle<R>,
+    code: u8,
+) -> Result<()> {
+    let state = app_handle.state::<QrLoginState>();
+    crate::qr_login::submit_check_code(&state, code)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Ask another user to verify their identity in the DM with them.
+/// Progress is reported through `on_event` until the verification is done or cancelled.
+pub(crate) async fn request_user_verification(
+    on_event: Channel<UserVerificationEvent>,
+    user_id: OwnedUserId,
+) -> Result<()> {
+    crate::user_verification::request_user_verification(&user_id, on_event)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+/// Accept a verification request received from another user.
+/// Progress is reported through `on_event` until the verification is done or cancelled.
+pub(crate) async fn accept_user_verification(
+    on_event: Channel<UserVerificationEvent>,
+    user_id: OwnedUserId,
+    flow_id: String,
+) -> Result<()> {
+    crate::user_verification::accept_user_verification(&user_id, &flow_id, on_event)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+pub(crate) async fn start_sas_verification(user_id: OwnedUserId, flow_id: String) -> Result<()> {
+    crate::user_verification::start_sas_verification(&user_id, &flow_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+pub(crate) async fn get_user_verification_qr_code(
+    user_id: OwnedUserId,
+    flow_id: String,
+) -> Result<String> {
+    crate::user_verification::get_qr_code(&user_id, &flow_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+pub(crate) async fn scan_user_verification_qr_code(
+    user_id: OwnedUserId,
+    flow_id: String,
+    qr_code: String,
+) -> Result<()> {
+    crate::user_verification::scan_qr_code(&user_id, &flow_id, &qr_code)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+pub(crate) async fn confirm_user_verification(
+    user_id: OwnedUserId,
+    flow_id: String,
+    matches: bool,
+) -> Result<()> {
+    crate::user_verification::confirm_user_verification(&user_id, &flow_id, matches)
+        .await
+        .map_err(Into::into)
+}
+
+#[command(async)]
+pub(crate) async fn cancel_user_verification(user_id: OwnedUserId, flow_id: String) -> Result<()> {
+    crate::user_verification::cancel_user_verification(&user_id, &flow_id)
+        .await
+        .map_err(Into::into)
+}
+
 //
 // Helpers
 //
@@ -632,7 +1475,7 @@ impl From<ThumbnailDeserHelper> for Thumbnail {
 }
 
 /// Types of metadata for an attachment.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(
     rename_all = "camelCase",
     rename_all_fields = "camelCase",
@@ -685,7 +1528,7 @@ impl From<AttachmentInfoDeserHelper> for AttachmentInfo {
 }
 
 /// Base metadata about an image.
-#[derive(Debug, Clone, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct BaseImageInfoDeserHelper {
     /// The height of the image in pixels.
     pub height: Option<UInt>,
@@ -700,7 +1543,7 @@ pub struct BaseImageInfoDeserHelper {
 }
 
 /// Base metadata about a video.
-#[derive(Debug, Clone, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct BaseVideoInfoDeserHelper {
     /// The duration of the video.
     pub duration: Option<Duration>,
@@ -715,7 +1558,7 @@ pub struct BaseVideoInfoDeserHelper {
 }
 
 /// Base metadata about an audio clip.
-#[derive(Debug, Clone, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct BaseAudioInfoDeserHelper {
     /// The duration of the audio clip.
     pub duration: Option<Duration>,
@@ -728,7 +1571,7 @@ pub struct BaseAudioInfoDeserHelper {
 }
 
 /// Base metadata about a file.
-#[derive(Debug, Clone, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct BaseFileInfoDeserHelper {
     /// The size of the file in bytes.
     pub size: Option<UInt>,
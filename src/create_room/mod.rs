@@ -0,0 +1,158 @@
+use anyhow::anyhow;
+use matrix_sdk::ruma::api::client::room::create_room::{self, v3::RoomPreset};
+use matrix_sdk::ruma::events::room::avatar::RoomAvatarEventContent;
+use matrix_sdk::ruma::events::room::encryption::RoomEncryptionEventContent;
+use matrix_sdk::ruma::events::room::history_visibility::{
+    HistoryVisibility, RoomHistoryVisibilityEventContent,
+};
+use matrix_sdk::ruma::events::space::child::SpaceChildEventContent;
+use matrix_sdk::ruma::events::space::parent::SpaceParentEventContent;
+use matrix_sdk::ruma::events::{AnyInitialStateEvent, InitialStateEvent, StateEventType};
+use matrix_sdk::ruma::serde::Raw;
+use matrix_sdk::ruma::{OwnedMxcUri, OwnedRoomId, OwnedUserId, RoomVersionId};
+use serde::{Deserialize, Serialize};
+use serde_json::value::to_raw_value;
+use tracing::warn;
+
+use crate::utils::{get_client, get_room};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoomPresetOption {
+    /// Only invited users can join.
+    PrivateChat,
+    /// Like `PrivateChat`, but invited users get the same power level as the creator.
+    TrustedPrivateChat,
+    /// Anyone can join.
+    PublicChat,
+}
+
+impl From<RoomPresetOption> for RoomPreset {
+    fn from(preset: RoomPresetOption) -> Self {
+        match preset {
+            RoomPresetOption::PrivateChat => RoomPreset::PrivateChat,
+            RoomPresetOption::TrustedPrivateChat => RoomPreset::TrustedPrivateChat,
+            RoomPresetOption::PublicChat => RoomPreset::PublicChat,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateRoomOptions {
+    pub name: Option<String>,
+    pub preset: RoomPresetOption,
+    pub is_encrypted: bool,
+    /// The localpart of the main alias of the room, i.e. `foo` for `#foo:server`.
+    pub alias_localpart: Option<String>,
+    /// The homeserver default version is used if `None`.
+    pub room_version: Option<String>,
+    pub topic: Option<String>,
+    pub avatar: Option<OwnedMxcUri>,
+    #[serde(default)]
+    pub invited_user_ids: Vec<OwnedUserId>,
+    /// The space in which the room is added.
+    pub parent_space_id: Option<OwnedRoomId>,
+    pub history_visibility: Option<HistoryVisibility>,
+    /// Overrides of the `m.room.power_levels` content set by the preset.
+    pub power_level_overrides: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// The `m.room.encryption` initial state enabling end-to-end encryption.
+pub(crate) fn encryption_initial_state() -> Raw<AnyInitialStateEvent> {
+    InitialStateEvent::with_empty_state_key(RoomEncryptionEventContent::with_recommended_defaults())
+        .to_raw_any()
+}
+
+/// A newly created room.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedRoom {
+    pub room_id: OwnedRoomId,
+    /// Why the room couldn't be added to its parent space, if it failed.
+    pub space_error: Option<String>,
+}
+
+/// Create a room with the given options. Once the room is created, failing to add it to
+/// the parent space is reported in the result instead of failing.
+pub async fn create_room(options: CreateRoomOptions) -> anyhow::Result<CreatedRoom> {
+    let client = get_client()?;
+    let own_server = client
+        .user_id()
+        .ok_or(anyhow!("The client isn't logged in"))?
+        .server_name()
+        .to_owned();
+
+    // Adding the room to the space requires to be allowed to add its children.
+    let parent_space = match &options.parent_space_id {
+        Some(space_id) => {
+            let space = get_room(space_id)?;
+            let power_levels = space.power_levels().await?;
+            if !power_levels.user_can_send_state(space.own_user_id(), StateEventType::SpaceChild) {
+                return Err(anyhow!("You are not allowed to add rooms to this space"));
+            }
+            Some(space)
+        }
+        None => None,
+    };
+
+    let mut initial_states = Vec::new();
+    if options.is_encrypted {
+        initial_states.push(encryption_initial_state());
+    }
+    if let Some(history_visibility) = options.history_visibility {
+        initial_states.push(
+            InitialStateEvent::with_empty_state_key(RoomHistoryVisibilityEventContent::new(
+                history_visibility,
+            ))
+            .to_raw_any(),
+        );
+    }
+    if let Some(avatar) = options.avatar {
+        let mut content = RoomAvatarEventContent::new();
+        content.url = Some(avatar);
+        initial_states.push(InitialStateEvent::with_empty_state_key(content).to_raw_any());
+    }
+    if let Some(space_id) = &options.parent_space_id {
+        let mut content = SpaceParentEventContent::new(vec![own_server.clone()]);
+        content.canonical = true;
+        initial_states.push(InitialStateEvent::new(space_id.clone(), content).to_raw_any());
+    }
+
+    let mut request = create_room::v3::Request::new();
+    request.name = options.name;
+    request.topic = options.topic;
+    request.preset = Some(options.preset.into());
+    request.room_alias_name = options.alias_localpart.filter(|a| !a.is_empty());
+    request.room_version = options
+        .room_version
+        .map(RoomVersionId::try_from)
+        .transpose()?;
+    request.invite = options.invited_user_ids;
+    request.initial_state = initial_states;
+    request.power_level_content_override = options
+        .power_level_overrides
+        .map(|overrides| to_raw_value(&overrides).map(Raw::from_json))
+        .transpose()?;
+
+    let room = client.create_room(request).await?;
+    let room_id = room.room_id().to_owned();
+
+    let mut space_error = None;
+    if let Some(space) = parent_space
+        && let Err(e) = space
+            .send_state_event_for_key(&room_id, SpaceChildEventContent::new(vec![own_server]))
+            .await
+    {
+        warn!(
+            "Couldn't add room {room_id} to space {}. {e}",
+            space.room_id()
+        );
+        space_error = Some(e.to_string());
+    }
+
+    Ok(CreatedRoom {
+        room_id,
+        space_error,
+    })
+}
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use matrix_sdk::encryption::CrossSigningResetAuthType;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Runtime};
+
+use crate::uia::{UiaOutcome, UiaSessions};
+use crate::utils::get_client;
+
+/// Reset the cross-signing identity, losing access to the messages encrypted for the old one.
+/// On OAuth homeservers, the approval URL is emitted through
+/// `matrix-svelte://reset-cross-signing-url` and the reset completes once approved.
+pub async fn reset_cross_signing<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    sessions: &UiaSessions,
+) -> anyhow::Result<UiaOutcome> {
+    let Some(handle) = get_client()?.encryption().reset_identity().await? else {
+        return Ok(UiaOutcome::Done {
+            result: Value::Null,
+        });
+    };
+    if let CrossSigningResetAuthType::OAuth(info) = handle.auth_type() {
+        app_handle.emit(
+            "matrix-svelte://reset-cross-signing-url",
+            info.approval_url.to_string(),
+        )?;
+    }
+    let handle = Arc::new(handle);
+    sessions
+        .start(move |auth| {
+            let handle = handle.clone();
+            async move {
+                handle.reset(auth).await?;
+                Ok(())
+            }
+        })
+        .await
+}
@@ -0,0 +1,100 @@
+use anyhow::anyhow;
+use matrix_sdk::ruma::{DeviceId, MilliSecondsSinceUnixEpoch, OwnedDeviceId, UInt};
+use serde::Serialize;
+
+use crate::uia::{UiaOutcome, UiaSessions};
+use crate::utils::get_client;
+
+/// Devices not seen for this long are considered stale by default.
+pub const DEFAULT_STALE_THRESHOLD_DAYS: u64 = 90;
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// A device of the current user that hasn't been used for a long time.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StaleDevice {
+    pub device_id: OwnedDeviceId,
+    pub display_name: Option<String>,
+    /// `None` if the homeserver never saw the device.
+    pub last_seen_ts: Option<MilliSecondsSinceUnixEpoch>,
+    pub last_seen_ip: Option<String>,
+}
+
+pub async fn rename_device(device_id: &DeviceId, name: &str) -> anyhow::Result<()> {
+    get_client()?.rename_device(device_id, name).await?;
+    Ok(())
+}
+
+/// Sign out the given devices of the current user. The current device can't be deleted
+/// this way, as it would leave the app in a broken state.
+pub async fn delete_devices(
+    sessions: &UiaSessions,
+    device_ids: Vec<OwnedDeviceId>,
+) -> anyhow::Result<UiaOutcome> {
+    let client = get_client()?;
+    if client
+        .device_id()
+        .is_some_and(|own| device_ids.iter().any(|id| id == own))
+    {
+        return Err(anyhow!(
+            "The current device can't be deleted, log out instead"
+        ));
+    }
+    if device_ids.is_empty() {
+        return Ok(UiaOutcome::Done {
+            result: serde_json::Value::Null,
+        });
+    }
+    sessions
+        .start(move |auth| {
+            let device_ids = device_ids.clone();
+            async move {
+                get_client()?.delete_devices(&device_ids, auth).await?;
+                Ok(())
+            }
+        })
+        .await
+}
+
+/// Sign out every device of the current user but this one.
+pub async fn sign_out_other_devices(sessions: &UiaSessions) -> anyhow::Result<UiaOutcome> {
+    let client = get_client()?;
+    let own_device_id = client.device_id();
+    let device_ids = client
+        .devices()
+        .await?
+        .devices
+        .into_iter()
+        .map(|d| d.device_id)
+        .filter(|id| Some(&**id) != own_device_id)
+        .collect();
+    delete_devices(sessions, device_ids).await
+}
+
+/// The devices of the current user not seen for `threshold_days`, oldest first.
+pub async fn get_stale_devices(threshold_days: u64) -> anyhow::Result<Vec<StaleDevice>> {
+    let client = get_client()?;
+    let own_device_id = client.device_id();
+    let threshold = UInt::try_from(threshold_days.saturating_mul(DAY_MS))?;
+    let now = MilliSecondsSinceUnixEpoch::now().get();
+
+    let mut stale: Vec<StaleDevice> = client
+        .devices()
+        .await?
+        .devices
+        .into_iter()
+        .filter(|d| Some(&*d.device_id) != own_device_id)
+        .filter(|d| {
+            d.last_seen_ts
+                .is_none_or(|ts| now.saturating_sub(ts.get()) >= threshold)
+        })
+        .map(|d| StaleDevice {
+            device_id: d.device_id,
+            display_name: d.display_name,
+            last_seen_ts: d.last_seen_ts,
+            last_seen_ip: d.last_seen_ip,
+        })
+        .collect();
+    stale.sort_by_key(|d| d.last_seen_ts);
+    Ok(stale)
+}
@@ -0,0 +1,366 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::anyhow;
+use matrix_sdk::ruma::api::client::room::create_room::{self, v3::RoomPreset};
+use matrix_sdk::ruma::events::GlobalAccountDataEventType;
+use matrix_sdk::ruma::serde::Raw;
+use matrix_sdk::ruma::{OwnedRoomId, OwnedUserId, UserId};
+use matrix_sdk::{Client, RoomMemberships, RoomState};
+use serde::Serialize;
+use serde_json::value::to_raw_value;
+
+use crate::create_room::encryption_initial_state;
+use crate::utils::get_client;
+
+/// The content of the `m.direct` account data: room ids by user id.
+/// Keys are kept as strings, as other clients may have written invalid user ids.
+type DirectMap = BTreeMap<String, Vec<OwnedRoomId>>;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupDm {
+    pub room_id: OwnedRoomId,
+    /// Whether the room has just been created.
+    pub is_new: bool,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectCleanupResult {
+    /// The number of user entries merged into another one.
+    pub merged_users: usize,
+    /// The number of duplicated room ids removed.
+    pub removed_rooms: usize,
+    /// The room to use with each user, which is moved first in their entry.
+    pub canonical_rooms: BTreeMap<String, OwnedRoomId>,
+}
+
+/// What makes a DM room the best one to use with a user, from the least to the most important.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct DmRoomRank {
+    is_joined: bool,
+    /// The user is the only other member of the room.
+    is_one_to_one: bool,
+    is_encrypted: bool,
+}
+
+async fn get_direct_map(client: &Client) -> anyhow::Result<DirectMap> {
+    Ok(client
+        .account()
+        .account_data_raw(GlobalAccountDataEventType::Direct)
+        .await?
+        .map(|raw| raw.deserialize_as::<DirectMap>())
+        .transpose()?
+        .unwrap_or_default())
+}
+
+async fn set_direct_map(client: &Client, direct: &DirectMap) -> anyhow::Result<()> {
+    client
+        .account()
+        .set_account_data_raw(
+            GlobalAccountDataEventType::Direct,
+            Raw::from_json(to_raw_value(direct)?),
+        )
+        .await?;
+    Ok(())
+}
+
+/// The users we have a DM with, according to `m.direct`.
+pub(crate) async fn get_dm_partners(client: &Client) -> anyhow::Result<Vec<OwnedUserId>> {
+    Ok(get_direct_map(client)
+        .await?
+        .into_keys()
+        .filter_map(|key| UserId::parse(key.trim()).ok())
+        .collect())
+}
+
+/// Find a joined encrypted DM whose other members are exactly the given users.
+async fn find_group_dm(
+    client: &Client,
+    direct: &DirectMap,
+    user_ids: &BTreeSet<OwnedUserId>,
+) -> anyhow::Result<Option<OwnedRoomId>> {
+    // The room must be listed for each of the users.
+    let mut candidates: Option<BTreeSet<&OwnedRoomId>> = None;
+    for user_id in user_ids {
+        let rooms: BTreeSet<&OwnedRoomId> = direct
+            .get(user_id.as_str())
+            .map(|rooms| rooms.iter().collect())
+            .unwrap_or_default();
+        candidates = Some(match candidates {
+            Some(candidates) => candidates.intersection(&rooms).copied().collect(),
+            None => rooms,
+        });
+    }
+
+    for room_id in candidates.unwrap_or_default() {
+        let Some(room) = client.get_room(room_id) else {
+            continue;
+        };
+        if room.state() != RoomState::Joined
+            || !room.latest_encryption_state().await?.is_encrypted()
+        {
+            continue;
+        }
+        let members: BTreeSet<OwnedUserId> = room
+            .members(RoomMemberships::JOIN | RoomMemberships::INVITE)
+            .await?
+            .into_iter()
+            .map(|m| m.user_id().to_owned())
+            .filter(|id| id != room.own_user_id())
+            .collect();
+        if members == *user_ids {
+            return Ok(Some(room_id.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// Get the encrypted DM with exactly the given users, or create it.
+pub async fn get_or_create_group_dm(user_ids: Vec<OwnedUserId>) -> anyhow::Result<GroupDm> {
+    let client = get_client()?;
+    let own_user_id = client
+        .user_id()
+        .ok_or(anyhow!("The client isn't logged in"))?;
+    let user_ids: BTreeSet<OwnedUserId> = user_ids
+        .into_iter()
+        .filter(|id| id != own_user_id)
+        .collect();
+    if user_ids.is_empty() {
+        return Err(anyhow!("A DM needs at least one other user"));
+    }
+
+    let direct = get_direct_map(client).await?;
+    if let Some(room_id) = find_group_dm(client, &direct, &user_ids).await? {
+        return Ok(GroupDm {
+            room_id,
+            is_new: false,
+        });
+    }
+
+    let mut request = create_room::v3::Request::new();
+    request.is_direct = true;
+    request.preset = Some(RoomPreset::TrustedPrivateChat);
+    request.invite = user_ids.into_iter().collect();
+    request.initial_state = vec![encryption_initial_state()];
+    // The SDK adds the room to `m.direct` for the invited users when `is_direct` is set.
+    let room = client.create_room(request).await?;
+    Ok(GroupDm {
+        room_id: room.room_id().to_owned(),
+        is_new: true,
+    })
+}
+
+/// The key of a `m.direct` entry, so the entries of the same user are merged.
+/// Server names are case-insensitive, and some clients write padded or differently cased ids.
+fn normalized_key(key: &str) -> String {
+    let key = key.trim();
+    match UserId::parse(key) {
+        Ok(user_id) => format!(
+            "@{}:{}",
+            user_id.localpart(),
+            user_id.server_name().as_str().to_lowercase()
+        ),
+        Err(_) => key.to_owned(),
+    }
+}
+
+/// Merge the entries of the same user and remove their duplicated room ids.
+fn merge_entries(direct: &DirectMap) -> (DirectMap, DirectCleanupResult) {
+    let mut merged = DirectMap::new();
+    let mut result = DirectCleanupResult::default();
+    for (key, rooms) in direct {
+        let key = normalized_key(key);
+        if merged.contains_key(&key) {
+            result.merged_users += 1;
+        }
+        let entry = merged.entry(key).or_default();
+        for room_id in rooms {
+            if entry.contains(room_id) {
+                result.removed_rooms += 1;
+            } else {
+                entry.push(room_id.clone());
+            }
+        }
+    }
+    (merged, result)
+}
+
+/// Move the best ranked room first, the earliest one winning a tie.
+fn move_canonical_first(rooms: &mut [OwnedRoomId], ranks: &[DmRoomRank]) {
+    let best = ranks
+        .iter()
+        .enumerate()
+        .max_by(|(i, a), (j, b)| a.cmp(b).then(j.cmp(i)))
+        .map(|(i, _)| i);
+    if let Some(best) = best {
+        rooms[..=best].rotate_right(1);
+    }
+}
+
+async fn rank_dm_room(
+    client: &Client,
+    room_id: &OwnedRoomId,
+    user_key: &str,
+) -> anyhow::Result<DmRoomRank> {
+    let Some(room) = client.get_room(room_id) else {
+        return Ok(DmRoomRank {
+            is_joined: false,
+            is_one_to_one: false,
+            is_encrypted: false,
+        });
+    };
+    let is_joined = room.state() == RoomState::Joined;
+    let others: Vec<String> = room
+        .members(RoomMemberships::JOIN | RoomMemberships::INVITE)
+        .await?
+        .into_iter()
+        .filter(|m| m.user_id() != room.own_user_id())
+        .map(|m| normalized_key(m.user_id().as_str()))
+        .collect();
+    Ok(DmRoomRank {
+        is_joined,
+        is_one_to_one: others == [user_key],
+        is_encrypted: room.latest_encryption_state().await?.is_encrypted(),
+    })
+}
+
+/// Merge the `m.direct` entries of the same user, remove duplicated room ids, and move
+/// the room to use with each user first in their entry: a joined one-to-one room,
+/// preferably encrypted. The account data is only written back if something changed.
+pub async fn merge_duplicate_direct_entries() -> anyhow::Result<DirectCleanupResult> {
+    let client = get_client()?;
+    let direct = get_direct_map(client).await?;
+
+    let (mut merged, mut result) = merge_entries(&direct);
+    for (key, rooms) in merged.iter_mut() {
+        let mut ranks = Vec::with_capacity(rooms.len());
+        for room_id in rooms.iter() {
+            ranks.push(rank_dm_room(client, room_id, key).await?);
+        }
+        move_canonical_first(rooms, &ranks);
+        if let Some(canonical) = rooms.first() {
+            result
+                .canonical_rooms
+                .insert(key.clone(), canonical.clone());
+        }
+    }
+
+    if merged != direct {
+        set_direct_map(client, &merged).await?;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room_id(id: &str) -> OwnedRoomId {
+        OwnedRoomId::try_from(id).unwrap()
+    }
+
+    fn rank(is_joined: bool, is_one_to_one: bool, is_encrypted: bool) -> DmRoomRank {
+        DmRoomRank {
+            is_joined,
+            is_one_to_one,
+            is_encrypted,
+        }
+    }
+
+    #[test]
+    fn normalizes_the_server_name_and_padding() {
+        assert_eq!(normalized_key(" @Alice:Example.ORG "), "@Alice:example.org");
+        assert_eq!(normalized_key("not a user id "), "not a user id");
+    }
+
+    #[test]
+    fn merges_the_entries_of_the_same_user() {
+        let direct = DirectMap::from([
+            (
+                "@alice:example.org".to_owned(),
+                vec![room_id("!a:example.org"), room_id("!b:example.org")],
+            ),
+            (
+                "@alice:EXAMPLE.org".to_owned(),
+                vec![room_id("!b:example.org"), room_id("!c:example.org")],
+            ),
+            (
+                "@bob:example.org".to_owned(),
+                vec![room_id("!d:example.org"), room_id("!d:example.org")],
+            ),
+        ]);
+        let (merged, result) = merge_entries(&direct);
+        assert_eq!(
+            merged,
+            DirectMap::from([
+                (
+                    "@alice:example.org".to_owned(),
+                    vec![
+                        room_id("!b:example.org"),
+                        room_id("!c:example.org"),
+                        room_id("!a:example.org"),
+                    ],
+                ),
+                (
+                    "@bob:example.org".to_owned(),
+                    vec![room_id("!d:example.org")]
+                ),
+            ])
+        );
+        assert_eq!(result.merged_users, 1);
+        assert_eq!(result.removed_rooms, 2);
+    }
+
+    #[test]
+    fn keeps_a_clean_map_as_is() {
+        let direct = DirectMap::from([(
+            "@alice:example.org".to_owned(),
+            vec![room_id("!a:example.org")],
+        )]);
+        let (merged, result) = merge_entries(&direct);
+        assert_eq!(merged, direct);
+        assert_eq!(result, DirectCleanupResult::default());
+    }
+
+    #[test]
+    fn moves_the_best_room_first() {
+        let mut rooms = vec![
+            room_id("!left:example.org"),
+            room_id("!group:example.org"),
+            room_id("!dm:example.org"),
+            room_id("!other_dm:example.org"),
+        ];
+        move_canonical_first(
+            &mut rooms,
+            &[
+                rank(false, true, true),
+                rank(true, false, true),
+                rank(true, true, true),
+                rank(true, true, true),
+            ],
+        );
+        assert_eq!(
+            rooms,
+            [
+                room_id("!dm:example.org"),
+                room_id("!left:example.org"),
+                room_id("!group:example.org"),
+                room_id("!other_dm:example.org"),
+            ]
+        );
+    }
+
+    #[test]
+    fn prefers_an_encrypted_room() {
+        let mut rooms = vec![room_id("!plain:example.org"), room_id("!e2ee:example.org")];
+        move_canonical_first(
+            &mut rooms,
+            &[rank(true, true, false), rank(true, true, true)],
+        );
+        assert_eq!(
+            rooms,
+            [room_id("!e2ee:example.org"), room_id("!plain:example.org")]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::anyhow;
+use matrix_sdk::deserialized_responses::TimelineEvent;
+use matrix_sdk::room::{IncludeRelations, RelationsOptions};
+use matrix_sdk::ruma::events::relation::RelationType;
+use matrix_sdk::ruma::events::room::message::{MessageType, Relation, RoomMessageEventContent};
+use matrix_sdk::ruma::events::{
+    AnySyncMessageLikeEvent, AnySyncTimelineEvent, SyncMessageLikeEvent,
+};
+use matrix_sdk::ruma::{EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedUserId, RoomId};
+use serde::Serialize;
+
+use crate::formatting::sanitize::sanitize_html;
+use crate::utils::get_client;
+
+/// A version of an edited message.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageRevision {
+    /// The id of the original event, or of the `m.replace` event for edits.
+    pub event_id: OwnedEventId,
+    pub timestamp: MilliSecondsSinceUnixEpoch,
+    pub body: String,
+    /// The HTML body, if the revision has one.
+    pub formatted_body: Option<String>,
+    /// The HTML body restricted to what the spec allows, safe to display as is.
+    pub sanitized_formatted_body: Option<String>,
+    pub is_original: bool,
+}
+
+/// Get all the revisions of a message, from the original one to the latest edit.
+pub async fn get_edit_history(
+    room_id: &RoomId,
+    event_id: &EventId,
+) -> anyhow::Result<Vec<MessageRevision>> {
+    let room = get_client()?
+        .get_room(room_id)
+        .ok_or(anyhow!("Room {room_id} not found"))?;
+
+    let original = room.event(event_id, None).await?;
+    let (original_sender, original_revision) = parse_original(original)?;
+    let mut revisions = vec![original_revision];
+
+    let mut from = None;
+    loop {
+        let relations = room
+            .relations(
+                event_id.to_owned(),
+                RelationsOptions {
+                    from,
+                    include_relations: IncludeRelations::RelationsOfType(RelationType::Replacement),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        revisions.extend(
+            relations
+                .chunk
+                .into_iter()
+                .filter_map(|event| parse_edit(event, &original_sender)),
+        );
+
+        match relations.next_batch_token {
+            Some(token) => from = Some(token),
+            None => break,
+        }
+    }
+
+    revisions.sort_by_key(|r| (!r.is_original, r.timestamp));
+    Ok(revisions)
+}
+
+fn original_message(
+    event: &TimelineEvent,
+) -> Option<SyncMessageLikeEvent<RoomMessageEventContent>> {
+    match event.raw().deserialize().ok()? {
+        AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(message)) => {
+            Some(message)
+        }
+        _ => None,
+    }
+}
+
+fn parse_original(event: TimelineEvent) -> anyhow::Result<(OwnedUserId, MessageRevision)> {
+    let Some(SyncMessageLikeEvent::Original(message)) = original_message(&event) else {
+        return Err(anyhow!("This event is not a message, or has been redacted"));
+    };
+    let revision = revision(
+        message.event_id,
+        message.origin_server_ts,
+        &message.content.msgtype,
+        true,
+    );
+    Ok((message.sender, revision))
+}
+
+fn parse_edit(event: TimelineEvent, original_sender: &OwnedUserId) -> Option<MessageRevision> {
+    let SyncMessageLikeEvent::Original(message) = original_message(&event)? else {
+        return None;
+    };
+    // Edits from another sender than the original one must be ignored.
+    if &message.sender != original_sender {
+        return None;
+    }
+    let Some(Relation::Replacement(replacement)) = message.content.relates_to else {
+        return None;
+    };
+    Some(revision(
+        message.event_id,
+        message.origin_server_ts,
+        &replacement.new_content.msgtype,
+        false,
+    ))
+}
+
+fn revision(
+    event_id: OwnedEventId,
+    timestamp: MilliSecondsSinceUnixEpoch,
+    msgtype: &MessageType,
+    is_original: bool,
+) -> MessageRevision {
+    let formatted = match msgtype {
+        MessageType::Text(c) => c.formatted.as_ref(),
+        MessageType::Notice(c) => c.formatted.as_ref(),
+        MessageType::Emote(c) => c.formatted.as_ref(),
+        _ => None,
+    };
+    let formatted_body = formatted.map(|f| f.body.clone());
+    MessageRevision {
+        event_id,
+        timestamp,
+        body: msgtype.body().to_owned(),
+        sanitized_formatted_body: formatted_body.as_deref().map(sanitize_html),
+        formatted_body,
+        is_original,
+    }
+}
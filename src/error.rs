@@ -1,3 +1,5 @@
+use std::fmt;
+
 use serde::{Serialize, ser::Serializer};
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -8,6 +10,8 @@ pub enum Error {
     Io(#[from] std::io::Error),
     #[error("No session found")]
     NoSessionFound,
+    #[error("The homeserver doesn't support {0}")]
+    UnsupportedEndpoint(Endpoint),
     #[error(transparent)]
     Anyhow(#[from] anyhow::Error),
     #[error(transparent)]
@@ -21,11 +25,48 @@ pub enum Error {
     PluginInvoke(#[from] tauri::plugin::mobile::PluginInvokeError),
 }
 
+/// An optional homeserver endpoint the frontend may have to work without.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Endpoint {
+    EventReports,
+    RoomReports,
+    UserReports,
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Endpoint::EventReports => "event reports",
+            Endpoint::RoomReports => "room reports",
+            Endpoint::UserReports => "user reports",
+        })
+    }
+}
+
+/// Errors the frontend is expected to branch on, serialized with a stable `kind`.
+#[derive(Serialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+enum TypedError {
+    UnsupportedEndpoint { endpoint: Endpoint, message: String },
+}
+
 impl Serialize for Error {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        match self {
+            Error::UnsupportedEndpoint(endpoint) => TypedError::UnsupportedEndpoint {
+                endpoint: *endpoint,
+                message: self.to_string(),
+            }
+            .serialize(serializer),
+            _ => serializer.serialize_str(self.to_string().as_ref()),
+        }
     }
 }
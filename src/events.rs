@@ -48,6 +48,8 @@ pub async fn event_forwarder<R: Runtime>(
                 }
             },
             EmitEvent::OAuthUrl(e) => {
+                #[cfg(desktop)]
+                crate::loopback::listen_for_oauth_redirect(&app_handle);
                 app_handle.emit("matrix-svelte://oauth-url", e)?;
             }
             EmitEvent::ResetCrossSigngingUrl(url) => {
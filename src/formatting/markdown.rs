@@ -0,0 +1,531 @@
+use std::collections::HashMap;
+
+use pulldown_cmark::{Alignment, CowStr, Event, LinkType, Options, Parser, Tag, TagEnd, html};
+
+use super::sanitize::is_allowed_link;
+
+const MATRIX_TO_BASE_URL: &str = "https://matrix.to/#/";
+const SPOILER_MARKER: &str = "||";
+const ROOM_MENTION: &str = "@room";
+const MXC_SCHEME: &str = "mxc://";
+
+/// The result of rendering composer Markdown to Matrix HTML.
+#[derive(Debug, Default, PartialEq)]
+pub struct RenderedMarkdown {
+    /// `None` if the Markdown doesn't contain any formatting.
+    pub html: Option<String>,
+    /// Mentioned user ids, in order of appearance and without duplicates.
+    pub user_ids: Vec<String>,
+    /// Whether `@room` has been mentioned.
+    pub room: bool,
+}
+
+enum Piece<'a> {
+    Event(Event<'a>),
+    /// A `||` marker, that becomes a spoiler boundary if it has a matching marker
+    /// in the same block.
+    SpoilerMarker,
+}
+
+/// Renders Markdown to HTML restricted to the tags allowed by the Matrix spec.
+///
+/// On top of CommonMark (with strikethrough and tables), this supports:
+/// - user (`@user:server`) and room alias (`#room:server`) pills, rendered as matrix.to links,
+/// - `@room` mentions,
+/// - spoilers, with the `||spoiler||` syntax.
+///
+/// Raw HTML in the input is escaped, links without an allowed scheme and images that aren't
+/// from the media repository are rendered as their text. `display_names` is used for the
+/// text of user pills.
+pub fn render_markdown(text: &str, display_names: &HashMap<String, String>) -> RenderedMarkdown {
+    let mut rendered = RenderedMarkdown::default();
+    let mut pieces: Vec<Piece> = Vec::new();
+    // Index of the first piece of the current block, for spoiler markers pairing.
+    let mut block_start = 0;
+    let mut in_code_block = false;
+    let mut link_depth = 0;
+    // Whether each opened link or image is rendered, to drop the matching end tags.
+    let mut kept_links = Vec::new();
+    let mut kept_images = Vec::new();
+
+    let parser = Parser::new_ext(text, Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES);
+    for event in parser {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                in_code_block = true;
+                pieces.push(Piece::Event(Event::Start(Tag::CodeBlock(kind))));
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                in_code_block = false;
+                pieces.push(Piece::Event(Event::End(TagEnd::CodeBlock)));
+                block_start = pieces.len();
+            }
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => {
+                link_depth += 1;
+                if let Some(user_id) = dest_url
+                    .strip_prefix(MATRIX_TO_BASE_URL)
+                    .filter(|id| is_user_id(id))
+                {
+                    push_unique(&mut rendered.user_ids, user_id);
+                }
+                let is_kept = is_allowed_link(&dest_url);
+                kept_links.push(is_kept);
+                if is_kept {
+                    pieces.push(Piece::Event(Event::Start(Tag::Link {
+                        link_type,
+                        dest_url,
+                        title,
+                        id,
+                    })));
+                }
+            }
+            Event::End(TagEnd::Link) => {
+                link_depth -= 1;
+                if kept_links.pop().unwrap_or_default() {
+                    pieces.push(Piece::Event(Event::End(TagEnd::Link)));
+                }
+            }
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => {
+                let is_kept = dest_url.starts_with(MXC_SCHEME);
+                kept_images.push(is_kept);
+                if is_kept {
+                    pieces.push(Piece::Event(Event::Start(Tag::Image {
+                        link_type,
+                        dest_url,
+                        title,
+                        id,
+                    })));
+                }
+            }
+            Event::End(TagEnd::Image) => {
+                if kept_images.pop().unwrap_or_default() {
+                    pieces.push(Piece::Event(Event::End(TagEnd::Image)));
+                }
+            }
+            // The alignment is rendered as a `style` attribute, which isn't allowed.
+            Event::Start(Tag::Table(alignments)) => {
+                let alignments = vec![Alignment::None; alignments.len()];
+                pieces.push(Piece::Event(Event::Start(Tag::Table(alignments))));
+            }
+            // Raw HTML isn't allowed, it is displayed as typed.
+            Event::Html(raw) | Event::InlineHtml(raw) => {
+                pieces.push(Piece::Event(Event::Text(raw)));
+            }
+            // Newlines in the composer are line breaks in the message.
+            Event::SoftBreak => pieces.push(Piece::Event(Event::HardBreak)),
+            Event::Text(content) if in_code_block => {
+                pieces.push(Piece::Event(Event::Text(content)));
+            }
+            Event::Text(content) => {
+                let mut first = true;
+                for part in content.split(SPOILER_MARKER) {
+                    if !first {
+                        pieces.push(Piece::SpoilerMarker);
+                    }
+                    first = false;
+                    if link_depth > 0 {
+                        pieces.push(Piece::Event(Event::Text(CowStr::from(part.to_owned()))));
+                    } else {
+                        push_text_with_pills(&mut pieces, &mut rendered, part, display_names);
+                    }
+                }
+            }
+            Event::End(tag_end) if is_block(&tag_end) => {
+                resolve_spoilers(&mut pieces[block_start..]);
+                pieces.push(Piece::Event(Event::End(tag_end)));
+                block_start = pieces.len();
+            }
+            Event::Start(tag) if is_block_start(&tag) => {
+                resolve_spoilers(&mut pieces[block_start..]);
+                pieces.push(Piece::Event(Event::Start(tag)));
+                block_start = pieces.len();
+            }
+            event => pieces.push(Piece::Event(event)),
+        }
+    }
+    resolve_spoilers(&mut pieces[block_start..]);
+
+    let events = pieces.into_iter().map(|piece| match piece {
+        Piece::Event(event) => event,
+        Piece::SpoilerMarker => Event::Text(CowStr::Borrowed(SPOILER_MARKER)),
+    });
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, events);
+
+    let plain_paragraph = format!("<p>{}</p>\n", escape_html(text.trim()));
+    if html_output != plain_paragraph {
+        rendered.html = Some(html_output.trim_end().to_owned());
+    }
+    rendered
+}
+
+/// Pairs the spoiler markers of a block. An unpaired marker stays as plain text.
+fn resolve_spoilers(pieces: &mut [Piece]) {
+    let markers: Vec<usize> = pieces
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| matches!(p, Piece::SpoilerMarker))
+        .map(|(i, _)| i)
+        .collect();
+    for pair in markers.chunks_exact(2) {
+        pieces[pair[0]] = Piece::Event(Event::InlineHtml(CowStr::Borrowed(
+            "<span data-mx-spoiler>",
+        )));
+        pieces[pair[1]] = Piece::Event(Event::InlineHtml(CowStr::Borrowed("</span>")));
+    }
+}
+
+fn is_block(tag_end: &TagEnd) -> bool {
+    matches!(
+        tag_end,
+        TagEnd::Paragraph
+            | TagEnd::Heading(_)
+            | TagEnd::BlockQuote(_)
+            | TagEnd::Item
+            | TagEnd::List(_)
+            | TagEnd::TableCell
+    )
+}
+
+fn is_block_start(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::Paragraph
+            | Tag::Heading { .. }
+            | Tag::BlockQuote(_)
+            | Tag::Item
+            | Tag::List(_)
+            | Tag::TableCell
+    )
+}
+
+fn push_unique(user_ids: &mut Vec<String>, user_id: &str) {
+    if !user_ids.iter().any(|u| u == user_id) {
+        user_ids.push(user_id.to_owned());
+    }
+}
+
+/// Splits a text in plain text and pills.
+fn push_text_with_pills<'a>(
+    pieces: &mut Vec<Piece<'a>>,
+    rendered: &mut RenderedMarkdown,
+    text: &str,
+    display_names: &HashMap<String, String>,
+) {
+    let mut plain_start = 0;
+    let mut cursor = 0;
+    while cursor < text.len() {
+        let rest = &text[cursor..];
+        let sigil = rest.chars().next().expect("rest is not empty");
+        let at_word_start = text[..cursor]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c.is_whitespace() || "([{<\"'".contains(c));
+
+        if at_word_start && (sigil == '@' || sigil == '#') {
+            if sigil == '@' && is_room_mention(rest) {
+                rendered.room = true;
+                cursor += ROOM_MENTION.len();
+                continue;
+            }
+            let id_len = matrix_id_len(rest);
+            if id_len > 0 {
+                let id = &rest[..id_len];
+                if cursor > plain_start {
+                    pieces.push(Piece::Event(Event::Text(CowStr::from(
+                        text[plain_start..cursor].to_owned(),
+                    ))));
+                }
+                let label = if sigil == '@' {
+                    push_unique(&mut rendered.user_ids, id);
+                    display_names.get(id).cloned().unwrap_or(id.to_owned())
+                } else {
+                    id.to_owned()
+                };
+                pieces.push(Piece::Event(Event::Start(Tag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url: CowStr::from(format!("{MATRIX_TO_BASE_URL}{id}")),
+                    title: CowStr::Borrowed(""),
+                    id: CowStr::Borrowed(""),
+                })));
+                pieces.push(Piece::Event(Event::Text(CowStr::from(label))));
+                pieces.push(Piece::Event(Event::End(TagEnd::Link)));
+                cursor += id_len;
+                plain_start = cursor;
+                continue;
+            }
+        }
+        cursor += sigil.len_utf8();
+    }
+    if plain_start < text.len() {
+        pieces.push(Piece::Event(Event::Text(CowStr::from(
+            text[plain_start..].to_owned(),
+        ))));
+    }
+}
+
+fn is_room_mention(text: &str) -> bool {
+    text.strip_prefix(ROOM_MENTION)
+        .is_some_and(|rest| rest.chars().next().is_none_or(|c| !is_localpart_char(c)))
+}
+
+fn is_localpart_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "._=-/+".contains(c)
+}
+
+fn is_server_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || ".-:[]".contains(c)
+}
+
+/// Length of the user id or room alias at the start of `text`, or 0 if there is none.
+/// Trailing punctuation (i.e. the final dot of a sentence) isn't part of the id.
+fn matrix_id_len(text: &str) -> usize {
+    let localpart_len = text[1..]
+        .find(|c: char| !is_localpart_char(c))
+        .map_or(text.len() - 1, |len| len);
+    if localpart_len == 0 || !text[1 + localpart_len..].starts_with(':') {
+        return 0;
+    }
+    let server_start = 1 + localpart_len + 1;
+    let server = &text[server_start..];
+    let server_len = server
+        .find(|c: char| !is_server_name_char(c))
+        .unwrap_or(server.len());
+    let server_len = server[..server_len].trim_end_matches(['.', ':', '-']).len();
+    if server_len == 0 {
+        return 0;
+    }
+    server_start + server_len
+}
+
+fn is_user_id(id: &str) -> bool {
+    id.starts_with('@') && matrix_id_len(id) == id.len()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(text: &str) -> RenderedMarkdown {
+        render_markdown(text, &HashMap::new())
+    }
+
+    fn html(text: &str) -> Option<String> {
+        render(text).html
+    }
+
+    #[test]
+    fn plain_text_has_no_html() {
+        assert_eq!(html("Hello world"), None);
+        assert_eq!(html("  Hello world  "), None);
+    }
+
+    #[test]
+    fn renders_formatting() {
+        assert_eq!(
+            html("**bold** _italic_ ~~struck~~ `code`").as_deref(),
+            Some(
+                "<p><strong>bold</strong> <em>italic</em> <del>struck</del> <code>code</code></p>"
+            )
+        );
+        assert_eq!(
+            html("first\nsecond").as_deref(),
+            Some("<p>first<br />\nsecond</p>")
+        );
+    }
+
+    #[test]
+    fn escapes_raw_html() {
+        assert_eq!(
+            html("<script>alert(1)</script>").as_deref(),
+            Some("&lt;script&gt;alert(1)&lt;/script&gt;")
+        );
+        // Escaped inline HTML is the same as the plain body.
+        assert_eq!(html("a <b>b</b>"), None);
+    }
+
+    #[test]
+    fn renders_user_pills_with_display_names() {
+        let display_names = HashMap::from([("@alice:example.org".to_owned(), "Alice".to_owned())]);
+        let rendered = render_markdown(
+            "Hi @alice:example.org and @bob:example.org.",
+            &display_names,
+        );
+        assert_eq!(
+            rendered.html.as_deref(),
+            Some(
+                "<p>Hi <a href=\"https://matrix.to/#/@alice:example.org\">Alice</a> and \
+                <a href=\"https://matrix.to/#/@bob:example.org\">@bob:example.org</a>.</p>"
+            )
+        );
+        assert_eq!(
+            rendered.user_ids,
+            vec!["@alice:example.org", "@bob:example.org"]
+        );
+        assert!(!rendered.room);
+    }
+
+    #[test]
+    fn deduplicates_mentions() {
+        let rendered = render("@alice:example.org @alice:example.org");
+        assert_eq!(rendered.user_ids, vec!["@alice:example.org"]);
+    }
+
+    #[test]
+    fn renders_room_alias_pills() {
+        assert_eq!(
+            html("Join #room:example.org").as_deref(),
+            Some(
+                "<p>Join <a href=\"https://matrix.to/#/#room:example.org\">#room:example.org</a></p>"
+            )
+        );
+    }
+
+    #[test]
+    fn ignores_ids_inside_words_and_code() {
+        assert_eq!(html("mail@alice:example.org"), None);
+        assert_eq!(
+            html("`@alice:example.org`").as_deref(),
+            Some("<p><code>@alice:example.org</code></p>")
+        );
+        assert!(render("`@alice:example.org`").user_ids.is_empty());
+    }
+
+    #[test]
+    fn detects_room_mentions() {
+        assert!(render("Hello @room!").room);
+        assert!(!render("Hello @roommates").room);
+        assert!(!render("Hello everyone").room);
+    }
+
+    #[test]
+    fn renders_spoilers() {
+        assert_eq!(
+            html("The end is ||a secret|| right?").as_deref(),
+            Some("<p>The end is <span data-mx-spoiler>a secret</span> right?</p>")
+        );
+    }
+
+    #[test]
+    fn keeps_unpaired_spoiler_markers() {
+        assert_eq!(html("a || b"), None);
+        assert_eq!(
+            html("||one|| ||two").as_deref(),
+            Some("<p><span data-mx-spoiler>one</span> ||two</p>")
+        );
+    }
+
+    #[test]
+    fn does_not_pair_spoilers_across_blocks() {
+        assert_eq!(
+            html("||one\n\ntwo||").as_deref(),
+            Some("<p>||one</p>\n<p>two||</p>")
+        );
+    }
+
+    #[test]
+    fn keeps_links_with_allowed_schemes() {
+        assert_eq!(
+            html("[site](https://example.org)").as_deref(),
+            Some("<p><a href=\"https://example.org\">site</a></p>")
+        );
+        assert_eq!(
+            html("[mail](mailto:alice@example.org)").as_deref(),
+            Some("<p><a href=\"mailto:alice@example.org\">mail</a></p>")
+        );
+    }
+
+    #[test]
+    fn renders_links_with_other_schemes_as_text() {
+        assert_eq!(
+            html("[click](javascript:alert(1))").as_deref(),
+            Some("<p>click</p>")
+        );
+        assert_eq!(
+            html("[page](relative/page)").as_deref(),
+            Some("<p>page</p>")
+        );
+    }
+
+    #[test]
+    fn only_keeps_mxc_images() {
+        assert_eq!(
+            html("![cat](mxc://example.org/abc)").as_deref(),
+            Some("<p><img src=\"mxc://example.org/abc\" alt=\"cat\" /></p>")
+        );
+        assert_eq!(
+            html("![tracker](https://example.org/pixel.png)").as_deref(),
+            Some("<p>tracker</p>")
+        );
+    }
+
+    #[test]
+    fn renders_tables_without_alignment_style() {
+        let html = html("| a | b |\n|:--|--:|\n| 1 | 2 |").expect("a table is formatted");
+        assert!(html.starts_with("<table>"));
+        assert!(!html.contains("style"));
+    }
+
+    #[test]
+    fn measures_matrix_ids() {
+        assert_eq!(matrix_id_len("@alice:example.org"), 18);
+        assert_eq!(matrix_id_len("@alice:example.org."), 18);
+        assert_eq!(matrix_id_len("@alice:example.org:8448, hi"), 23);
+        assert_eq!(matrix_id_len("#room:example.org)"), 17);
+        assert_eq!(matrix_id_len("@alice:[::1]:8448"), 17);
+    }
+
+    #[test]
+    fn rejects_incomplete_matrix_ids() {
+        assert_eq!(matrix_id_len("@alice"), 0);
+        assert_eq!(matrix_id_len("@alice:"), 0);
+        assert_eq!(matrix_id_len("@:example.org"), 0);
+        assert_eq!(matrix_id_len("@alice:."), 0);
+        assert_eq!(matrix_id_len("@"), 0);
+    }
+
+    #[test]
+    fn recognizes_user_ids() {
+        assert!(is_user_id("@alice:example.org"));
+        assert!(!is_user_id("#room:example.org"));
+        assert!(!is_user_id("@alice:example.org/extra"));
+        assert!(!is_user_id("@alice"));
+    }
+
+    #[test]
+    fn pairs_spoiler_markers() {
+        let mut pieces = vec![
+            Piece::SpoilerMarker,
+            Piece::Event(Event::Text(CowStr::Borrowed("a"))),
+            Piece::SpoilerMarker,
+            Piece::SpoilerMarker,
+        ];
+        resolve_spoilers(&mut pieces);
+        let html: Vec<String> = pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Event(Event::InlineHtml(html)) => html.to_string(),
+                Piece::Event(Event::Text(text)) => text.to_string(),
+                Piece::Event(_) => "event".to_owned(),
+                Piece::SpoilerMarker => SPOILER_MARKER.to_owned(),
+            })
+            .collect();
+        assert_eq!(html, vec!["<span data-mx-spoiler>", "a", "</span>", "||"]);
+    }
+}
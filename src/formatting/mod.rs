@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use matrix_sdk::ruma::events::Mentions;
+use matrix_sdk::ruma::events::room::message::FormattedBody;
+use matrix_sdk::ruma::{RoomId, UserId};
+use serde::Serialize;
+
+mod markdown;
+pub mod sanitize;
+
+/// A composer message, ready to be used in a `m.room.message` content.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormattedMessage {
+    /// The plain text fallback, which is the Markdown as typed.
+    pub body: String,
+    /// `None` if the message doesn't contain any formatting.
+    pub formatted: Option<FormattedBody>,
+    pub mentions: Mentions,
+}
+
+/// Converts the Markdown typed in the composer to `org.matrix.custom.html`.
+/// If a room is given, the user pills are labelled with the members display names.
+pub async fn format_markdown(
+    room_id: Option<&RoomId>,
+    text: &str,
+) -> anyhow::Result<FormattedMessage> {
+    let mut rendered = markdown::render_markdown(text, &HashMap::new());
+
+    if let Some(room_id) = room_id
+        && !rendered.user_ids.is_empty()
+    {
+        let room = crate::utils::get_client()?
+            .get_room(room_id)
+            .ok_or(anyhow!("Room {room_id} not found"))?;
+        let mut display_names = HashMap::new();
+        for user_id in &rendered.user_ids {
+            let Ok(user_id) = UserId::parse(user_id) else {
+                continue;
+            };
+            if let Ok(Some(member)) = room.get_member_no_sync(&user_id).await
+                && let Some(name) = member.display_name()
+            {
+                display_names.insert(user_id.to_string(), name.to_owned());
+            }
+        }
+        if !display_names.is_empty() {
+            rendered = markdown::render_markdown(text, &display_names);
+        }
+    }
+
+    let mut mentions = Mentions::with_user_ids(
+        rendered
+            .user_ids
+            .iter()
+            .filter_map(|user_id| UserId::parse(user_id).ok()),
+    );
+    mentions.room = rendered.room;
+
+    Ok(FormattedMessage {
+        body: text.to_owned(),
+        formatted: rendered.html.map(FormattedBody::html),
+        mentions,
+    })
+}
@@ -0,0 +1,375 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+use ammonia::Builder;
+use serde_json::Value;
+
+const HTML_FORMAT: &str = "org.matrix.custom.html";
+/// The field added next to `formatted_body` in the timeline items.
+const SANITIZED_FIELD: &str = "sanitized_formatted_body";
+/// The HTML preview of the latest message of a thread, only used for display.
+const THREAD_SUMMARY_POINTER: &str = "/data/threadSummary/eventFormattedSummary";
+
+/// Tags allowed by the spec in `org.matrix.custom.html` bodies.
+const ALLOWED_TAGS: &[&str] = &[
+    "font",
+    "del",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "blockquote",
+    "p",
+    "a",
+    "ul",
+    "ol",
+    "sup",
+    "sub",
+    "li",
+    "b",
+    "i",
+    "u",
+    "strong",
+    "em",
+    "s",
+    "code",
+    "hr",
+    "br",
+    "div",
+    "table",
+    "thead",
+    "tbody",
+    "tr",
+    "th",
+    "td",
+    "caption",
+    "pre",
+    "span",
+    "img",
+    "details",
+    "summary",
+];
+
+const LINK_SCHEMES: &[&str] = &["https", "http", "ftp", "mailto", "magnet"];
+
+static SANITIZER: LazyLock<Builder<'static>> = LazyLock::new(|| {
+    let tag_attributes = HashMap::from([
+        (
+            "font",
+            HashSet::from(["data-mx-bg-color", "data-mx-color", "color"]),
+        ),
+        (
+            "span",
+            HashSet::from([
+                "data-mx-bg-color",
+                "data-mx-color",
+                "data-mx-spoiler",
+                "data-mx-maths",
+            ]),
+        ),
+        ("a", HashSet::from(["target", "href"])),
+        (
+            "img",
+            HashSet::from(["width", "height", "alt", "title", "src"]),
+        ),
+        ("ol", HashSet::from(["start"])),
+        ("code", HashSet::from(["class"])),
+        ("div", HashSet::from(["data-mx-maths"])),
+    ]);
+
+    let mut builder = Builder::default();
+    builder
+        .tags(ALLOWED_TAGS.iter().copied().collect())
+        .tag_attributes(tag_attributes)
+        .generic_attributes(HashSet::new())
+        // Reply fallbacks are removed with their content.
+        .clean_content_tags(HashSet::from(["mx-reply", "script", "style"]))
+        // Schemes are checked again per attribute in the filter below.
+        .url_schemes(LINK_SCHEMES.iter().copied().chain(["mxc"]).collect())
+        .url_relative(ammonia::UrlRelative::Deny)
+        .link_rel(Some("noopener noreferrer"))
+        .attribute_filter(filter_attribute);
+    builder
+});
+
+fn filter_attribute<'a>(element: &str, attribute: &str, value: &'a str) -> Option<Cow<'a, str>> {
+    match (element, attribute) {
+        ("a", "href") => is_allowed_link(value).then_some(Cow::Borrowed(value)),
+        // Images are only allowed from the media repository, through the plugin's media scheme.
+        ("img", "src") => value
+            .strip_prefix("mxc://")
+            .map(|path| Cow::Owned(media_scheme_uri(path))),
+        ("code", "class") => value
+            .split_whitespace()
+            .find(|class| class.starts_with("language-"))
+            .map(|class| Cow::Owned(class.to_owned())),
+        (_, "color" | "data-mx-color" | "data-mx-bg-color") => {
+            is_hex_color(value).then_some(Cow::Borrowed(value))
+        }
+        _ => Some(Cow::Borrowed(value)),
+    }
+}
+
+/// Whether a link uses one of the schemes allowed by the spec.
+pub(crate) fn is_allowed_link(href: &str) -> bool {
+    LINK_SCHEMES.iter().any(|scheme| {
+        href.get(..scheme.len() + 1)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(&format!("{scheme}:")))
+    })
+}
+
+/// Android and Windows webviews can't use a custom protocol directly,
+/// so the media scheme is served from `http://mxc.localhost` there.
+fn media_scheme_uri(path: &str) -> String {
+    if cfg!(any(target_os = "android", target_os = "windows")) {
+        format!("http://mxc.localhost/{path}")
+    } else {
+        format!("mxc://{path}")
+    }
+}
+
+fn is_hex_color(value: &str) -> bool {
+    value
+        .strip_prefix('#')
+        .is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Sanitizes a `org.matrix.custom.html` body to the tags and attributes allowed by the spec,
+/// and removes its reply fallback.
+pub fn sanitize_html(html: &str) -> String {
+    SANITIZER.clean(html).to_string()
+}
+
+/// Replaces an HTML string, only used for display, by its sanitized version.
+fn sanitize_in_place(html: Option<&mut Value>) {
+    if let Some(html) = html
+        && let Some(sanitized) = html.as_str().map(sanitize_html)
+    {
+        *html = Value::String(sanitized);
+    }
+}
+
+/// Adds the sanitized HTML next to the original `formatted_body` of a serialized
+/// timeline item, if it has one. The thread summary is sanitized in place.
+pub fn sanitize_timeline_item(item: &mut Value) {
+    if item.get("kind").and_then(Value::as_str) != Some("msgLike") {
+        return;
+    }
+    sanitize_in_place(item.pointer_mut(THREAD_SUMMARY_POINTER));
+    let Some(body) = item
+        .pointer_mut("/data/body")
+        .and_then(Value::as_object_mut)
+    else {
+        return;
+    };
+    if body.get("format").and_then(Value::as_str) != Some(HTML_FORMAT) {
+        return;
+    }
+    if let Some(formatted_body) = body.get("formatted_body").and_then(Value::as_str) {
+        let sanitized = sanitize_html(formatted_body);
+        body.insert(SANITIZED_FIELD.to_owned(), Value::String(sanitized));
+    }
+}
+
+/// Sanitizes all the timeline items of a serialized `RoomScreen`.
+pub fn sanitize_room_screen(room_screen: &mut Value) {
+    if let Some(items) = room_screen
+        .pointer_mut("/tlState/items")
+        .and_then(Value::as_array_mut)
+    {
+        items.iter_mut().for_each(sanitize_timeline_item);
+    }
+}
+
+/// Sanitizes in place the HTML preview of the latest message of each room of a serialized
+/// `RoomsList`.
+pub fn sanitize_rooms_list(rooms_list: &mut Value) {
+    for rooms in ["allJoinedRooms", "invitedRooms"] {
+        let Some(rooms) = rooms_list.get_mut(rooms).and_then(Value::as_object_mut) else {
+            continue;
+        };
+        for room in rooms.values_mut() {
+            sanitize_in_place(room.pointer_mut("/latest/1"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn removes_scripts_with_their_content() {
+        assert_eq!(
+            sanitize_html("<p>Hi</p><script>alert(1)</script><style>p {}</style>"),
+            "<p>Hi</p>"
+        );
+    }
+
+    #[test]
+    fn removes_the_reply_fallback() {
+        assert_eq!(
+            sanitize_html("<mx-reply><blockquote>Quoted</blockquote></mx-reply>Answer"),
+            "Answer"
+        );
+    }
+
+    #[test]
+    fn removes_disallowed_tags_but_keeps_their_text() {
+        assert_eq!(
+            sanitize_html("<iframe></iframe><marquee>Hello</marquee>"),
+            "Hello"
+        );
+    }
+
+    #[test]
+    fn removes_links_with_disallowed_schemes() {
+        assert_eq!(
+            sanitize_html("<a href=\"javascript:alert(1)\">click</a>"),
+            "<a rel=\"noopener noreferrer\">click</a>"
+        );
+        assert_eq!(
+            sanitize_html("<a href=\"JavaScript:alert(1)\">click</a>"),
+            "<a rel=\"noopener noreferrer\">click</a>"
+        );
+        assert_eq!(
+            sanitize_html("<a href=\"/relative\">click</a>"),
+            "<a rel=\"noopener noreferrer\">click</a>"
+        );
+    }
+
+    #[test]
+    fn keeps_links_with_allowed_schemes() {
+        assert_eq!(
+            sanitize_html("<a href=\"https://example.org\">site</a>"),
+            "<a href=\"https://example.org\" rel=\"noopener noreferrer\">site</a>"
+        );
+        assert_eq!(
+            sanitize_html("<a href=\"mailto:alice@example.org\">mail</a>"),
+            "<a href=\"mailto:alice@example.org\" rel=\"noopener noreferrer\">mail</a>"
+        );
+    }
+
+    #[test]
+    fn only_keeps_mxc_image_sources() {
+        assert_eq!(
+            sanitize_html("<img src=\"https://example.org/pixel.png\" alt=\"pixel\">"),
+            "<img alt=\"pixel\">"
+        );
+        assert_eq!(
+            sanitize_html("<img src=\"mxc://example.org/abc\">"),
+            format!("<img src=\"{}\">", media_scheme_uri("example.org/abc"))
+        );
+    }
+
+    #[test]
+    fn removes_event_handler_attributes() {
+        assert_eq!(
+            sanitize_html("<b onclick=\"alert(1)\">bold</b><img onerror=\"alert(1)\">"),
+            "<b>bold</b><img>"
+        );
+    }
+
+    #[test]
+    fn keeps_allowed_data_mx_attributes() {
+        assert_eq!(
+            sanitize_html("<span data-mx-spoiler=\"\">secret</span>"),
+            "<span data-mx-spoiler=\"\">secret</span>"
+        );
+        assert_eq!(
+            sanitize_html(
+                "<font data-mx-color=\"#ff0000\" data-mx-bg-color=\"#00FF00\">red</font>"
+            ),
+            "<font data-mx-color=\"#ff0000\" data-mx-bg-color=\"#00FF00\">red</font>"
+        );
+    }
+
+    #[test]
+    fn removes_invalid_or_unknown_data_mx_attributes() {
+        assert_eq!(
+            sanitize_html("<font data-mx-color=\"red;background:url(x)\">red</font>"),
+            "<font>red</font>"
+        );
+        assert_eq!(
+            sanitize_html("<span data-mx-unknown=\"1\">text</span>"),
+            "<span>text</span>"
+        );
+        assert_eq!(
+            sanitize_html("<p data-mx-spoiler=\"\">text</p>"),
+            "<p>text</p>"
+        );
+    }
+
+    #[test]
+    fn only_keeps_language_classes_on_code() {
+        assert_eq!(
+            sanitize_html("<code class=\"evil language-rust\">fn</code>"),
+            "<code class=\"language-rust\">fn</code>"
+        );
+        assert_eq!(sanitize_html("<p class=\"evil\">text</p>"), "<p>text</p>");
+    }
+
+    #[test]
+    fn adds_the_sanitized_body_to_html_timeline_items() {
+        let mut item = json!({
+            "kind": "msgLike",
+            "data": {
+                "body": {
+                    "body": "Hi",
+                    "format": "org.matrix.custom.html",
+                    "formatted_body": "<b onclick=\"alert(1)\">Hi</b>",
+                },
+                "threadSummary": {
+                    "eventFormattedSummary": "<img src=\"https://example.org/x.png\">Reply",
+                },
+            },
+        });
+        sanitize_timeline_item(&mut item);
+        assert_eq!(
+            item["data"]["body"]["formatted_body"],
+            "<b onclick=\"alert(1)\">Hi</b>"
+        );
+        assert_eq!(item["data"]["body"][SANITIZED_FIELD], "<b>Hi</b>");
+        assert_eq!(
+            item["data"]["threadSummary"]["eventFormattedSummary"],
+            "<img>Reply"
+        );
+    }
+
+    #[test]
+    fn ignores_plain_timeline_items() {
+        let mut item = json!({
+            "kind": "msgLike",
+            "data": { "body": { "body": "<b>Hi</b>" } },
+        });
+        sanitize_timeline_item(&mut item);
+        assert!(item["data"]["body"].get(SANITIZED_FIELD).is_none());
+    }
+
+    #[test]
+    fn sanitizes_the_latest_message_of_rooms() {
+        let mut rooms_list = json!({
+            "allJoinedRooms": {
+                "!a:example.org": { "latest": [1, "<script>x</script><b>Alice</b>: Hi"] },
+                "!b:example.org": { "latest": null },
+            },
+            "invitedRooms": {
+                "!c:example.org": { "latest": [2, "<i onmouseover=\"x\">Bob</i>"] },
+            },
+        });
+        sanitize_rooms_list(&mut rooms_list);
+        assert_eq!(
+            rooms_list["allJoinedRooms"]["!a:example.org"]["latest"][1],
+            "<b>Alice</b>: Hi"
+        );
+        assert_eq!(
+            rooms_list["invitedRooms"]["!c:example.org"]["latest"][1],
+            "<i>Bob</i>"
+        );
+    }
+}
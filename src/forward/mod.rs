@@ -0,0 +1,294 @@
+use std::io::Cursor;
+
+use anyhow::anyhow;
+use matrix_sdk::Client;
+use matrix_sdk::ruma::events::room::MediaSource;
+use matrix_sdk::ruma::events::room::message::{
+    MessageType, Relation, RoomMessageEventContent, SyncRoomMessageEvent,
+};
+use matrix_sdk::ruma::events::{AnySyncMessageLikeEvent, AnySyncTimelineEvent};
+use matrix_sdk::ruma::{EventId, OwnedRoomId, RoomId};
+use matrix_ui_serializable::{MatrixRequest, MediaFormat, MediaRequestParameters, oneshot};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::utils::get_client;
+
+const HTML_REPLY_FALLBACK_END: &str = "</mx-reply>";
+
+/// The result of forwarding an event to one room.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardResult {
+    pub room_id: OwnedRoomId,
+    /// `None` if the event has been forwarded successfully.
+    pub error: Option<String>,
+}
+
+/// Attachments of an encrypted source are downloaded once, then re-uploaded
+/// at most once in each form (encrypted or not) depending on the target rooms.
+#[derive(Default)]
+struct ReuploadCache {
+    decrypted: Option<Vec<u8>>,
+    encrypted: Option<MediaSource>,
+    plain: Option<MediaSource>,
+}
+
+pub async fn forward_event(
+    source_room_id: &RoomId,
+    event_id: &EventId,
+    target_room_ids: Vec<OwnedRoomId>,
+) -> anyhow::Result<Vec<ForwardResult>> {
+    let client = get_client()?;
+    let source_room = client
+        .get_room(source_room_id)
+        .ok_or(anyhow!("Room {source_room_id} not found"))?;
+
+    let event = source_room.event(event_id, None).await?;
+    let Ok(AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+        SyncRoomMessageEvent::Original(message),
+    ))) = event.raw().deserialize()
+    else {
+        return Err(anyhow!("Only messages can be forwarded"));
+    };
+    let msgtype = forwarded_msgtype(message.content);
+
+    let mut media_cache = ReuploadCache::default();
+    let mut results = Vec::with_capacity(target_room_ids.len());
+    for room_id in target_room_ids {
+        let error = forward_to_room(client, &room_id, msgtype.clone(), &mut media_cache)
+            .await
+            .err()
+            .map(|e| {
+                warn!("Couldn't forward event {event_id} to room {room_id}. {e}");
+                e.to_string()
+            });
+        results.push(ForwardResult { room_id, error });
+    }
+    Ok(results)
+}
+
+async fn forward_to_room(
+    client: &Client,
+    room_id: &RoomId,
+    mut msgtype: MessageType,
+    media_cache: &mut ReuploadCache,
+) -> anyhow::Result<()> {
+    let room = client
+        .get_room(room_id)
+        .ok_or(anyhow!("Room {room_id} not found"))?;
+    let target_is_encrypted = room.latest_encryption_state().await?.is_encrypted();
+
+    if let Some((source, mimetype)) = media_source_mut(&mut msgtype)
+        && let MediaSource::Encrypted(_) = source
+    {
+        *source = reupload(client, source, mimetype, target_is_encrypted, media_cache).await?;
+    }
+
+    room.send(RoomMessageEventContent::new(msgtype)).await?;
+    Ok(())
+}
+
+/// The source and mimetype of the main attachment of a message, if any.
+fn media_source_mut(msgtype: &mut MessageType) -> Option<(&mut MediaSource, Option<String>)> {
+    match msgtype {
+        MessageType::Image(c) => Some((
+            &mut c.source,
+            c.info.as_ref().and_then(|i| i.mimetype.clone()),
+        )),
+        MessageType::Video(c) => Some((
+            &mut c.source,
+            c.info.as_ref().and_then(|i| i.mimetype.clone()),
+        )),
+        MessageType::Audio(c) => Some((
+            &mut c.source,
+            c.info.as_ref().and_then(|i| i.mimetype.clone()),
+        )),
+        MessageType::File(c) => Some((
+            &mut c.source,
+            c.info.as_ref().and_then(|i| i.mimetype.clone()),
+        )),
+        _ => None,
+    }
+}
+
+/// An encrypted thumbnail contains the key of the source room media, so it isn't forwarded.
+/// Clients fall back to the main attachment.
+fn drop_encrypted_thumbnail(msgtype: &mut MessageType) {
+    let (thumbnail_source, thumbnail_info) = match msgtype {
+        MessageType::Image(c) => match c.info.as_deref_mut() {
+            Some(info) => (&mut info.thumbnail_source, &mut info.thumbnail_info),
+            None => return,
+        },
+        MessageType::Video(c) => match c.info.as_deref_mut() {
+            Some(info) => (&mut info.thumbnail_source, &mut info.thumbnail_info),
+            None => return,
+        },
+        MessageType::File(c) => match c.info.as_deref_mut() {
+            Some(info) => (&mut info.thumbnail_source, &mut info.thumbnail_info),
+            None => return,
+        },
+        _ => return,
+    };
+    if let Some(MediaSource::Encrypted(_)) = thumbnail_source {
+        *thumbnail_source = None;
+        *thumbnail_info = None;
+    }
+}
+
+/// Only the msgtype is kept, so replies and threads relations are dropped.
+fn forwarded_msgtype(content: RoomMessageEventContent) -> MessageType {
+    let mut msgtype = content.msgtype;
+    if is_reply(content.relates_to.as_ref()) {
+        strip_reply_fallback(&mut msgtype);
+    }
+    drop_encrypted_thumbnail(&mut msgtype);
+    msgtype
+}
+
+/// Only replies have a fallback, a thread message only replies when it isn't falling back.
+fn is_reply<C>(relates_to: Option<&Relation<C>>) -> bool {
+    match relates_to {
+        Some(Relation::Reply { .. }) => true,
+        Some(Relation::Thread(thread)) => thread.in_reply_to.is_some() && !thread.is_falling_back,
+        _ => false,
+    }
+}
+
+/// A forwarded reply isn't a reply anymore, so the quote of the replied message is removed.
+fn strip_reply_fallback(msgtype: &mut MessageType) {
+    let (body, formatted) = match msgtype {
+        MessageType::Text(c) => (&mut c.body, &mut c.formatted),
+        MessageType::Notice(c) => (&mut c.body, &mut c.formatted),
+        MessageType::Emote(c) => (&mut c.body, &mut c.formatted),
+        _ => return,
+    };
+    *body = remove_plain_reply_fallback(body).to_owned();
+    if let Some(formatted) = formatted {
+        formatted.body = remove_html_reply_fallback(&formatted.body).to_owned();
+    }
+}
+
+/// The plain fallback is made of `> ` lines, followed by an empty line.
+fn remove_plain_reply_fallback(body: &str) -> &str {
+    if !body.starts_with("> ") {
+        return body;
+    }
+    let mut rest = body;
+    while rest.starts_with('>') {
+        rest = rest.split_once('\n').map_or("", |(_, rest)| rest);
+    }
+    rest.strip_prefix('\n').unwrap_or(rest)
+}
+
+/// The HTML fallback is a `<mx-reply>` element at the start of the body.
+fn remove_html_reply_fallback(html: &str) -> &str {
+    if !html.trim_start().starts_with("<mx-reply>") {
+        return html;
+    }
+    html.find(HTML_REPLY_FALLBACK_END)
+        .map_or(html, |end| &html[end + HTML_REPLY_FALLBACK_END.len()..])
+}
+
+async fn reupload(
+    client: &Client,
+    source: &MediaSource,
+    mimetype: Option<String>,
+    target_is_encrypted: bool,
+    cache: &mut ReuploadCache,
+) -> anyhow::Result<MediaSource> {
+    if target_is_encrypted && let Some(source) = &cache.encrypted {
+        return Ok(source.clone());
+    }
+    if !target_is_encrypted && let Some(source) = &cache.plain {
+        return Ok(source.clone());
+    }
+
+    let data = match &cache.decrypted {
+        Some(data) => data.clone(),
+        None => {
+            let data = fetch_media(source.clone()).await?;
+            cache.decrypted = Some(data.clone());
+            data
+        }
+    };
+
+    if target_is_encrypted {
+        let file = client.upload_encrypted_file(&mut Cursor::new(data)).await?;
+        let new_source = MediaSource::Encrypted(Box::new(file));
+        cache.encrypted = Some(new_source.clone());
+        Ok(new_source)
+    } else {
+        let mime = mimetype
+            .and_then(|m| m.parse().ok())
+            .unwrap_or(mime::APPLICATION_OCTET_STREAM);
+        let response = client.media().upload(&mime, data, None).await?;
+        let new_source = MediaSource::Plain(response.content_uri);
+        cache.plain = Some(new_source.clone());
+        Ok(new_source)
+    }
+}
+
+/// Download and decrypt a media through the same path as the `fetch_media` command.
+async fn fetch_media(source: MediaSource) -> anyhow::Result<Vec<u8>> {
+    let (tx, rx) = oneshot::channel();
+    matrix_ui_serializable::commands::submit_async_request(MatrixRequest::FetchMedia {
+        media_request: MediaRequestParameters {
+            source,
+            format: MediaFormat::File,
+        },
+        content_sender: tx,
+    });
+    rx.await
+        .map_err(anyhow::Error::from)?
+        .map_err(anyhow::Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_the_plain_reply_fallback() {
+        let body = "> <@alice:example.org> Hello\n> second line\n\nHi Alice";
+        assert_eq!(remove_plain_reply_fallback(body), "Hi Alice");
+    }
+
+    #[test]
+    fn keeps_a_plain_body_without_fallback() {
+        assert_eq!(remove_plain_reply_fallback("Hi\n> quote"), "Hi\n> quote");
+        assert_eq!(remove_plain_reply_fallback(">not a quote"), ">not a quote");
+    }
+
+    #[test]
+    fn keeps_the_quote_of_a_message_without_reply() {
+        let body = "> a quote\n> on two lines\n\nand a comment";
+        let msgtype = forwarded_msgtype(RoomMessageEventContent::text_plain(body));
+        assert_eq!(msgtype.body(), body);
+    }
+
+    #[test]
+    fn strips_the_fallback_of_a_reply() {
+        let content: RoomMessageEventContent = serde_json::from_value(serde_json::json!({
+            "msgtype": "m.text",
+            "body": "> <@alice:example.org> Hello\n\nHi Alice",
+            "m.relates_to": { "m.in_reply_to": { "event_id": "$event:example.org" } },
+        }))
+        .unwrap();
+        assert_eq!(forwarded_msgtype(content).body(), "Hi Alice");
+    }
+
+    #[test]
+    fn removes_the_html_reply_fallback() {
+        let html = "<mx-reply><blockquote><a href=\"https://matrix.to/#/!room:example.org/$event\">\
+            In reply to</a> <a href=\"https://matrix.to/#/@alice:example.org\">@alice:example.org</a>\
+            <br>Hello</blockquote></mx-reply>Hi <b>Alice</b>";
+        assert_eq!(remove_html_reply_fallback(html), "Hi <b>Alice</b>");
+    }
+
+    #[test]
+    fn keeps_an_html_body_without_fallback() {
+        let html = "Hi <mx-reply>not a fallback</mx-reply>";
+        assert_eq!(remove_html_reply_fallback(html), html);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use matrix_sdk::ruma::events::GlobalAccountDataEvent;
+use matrix_sdk::ruma::events::ignored_user_list::IgnoredUserListEventContent;
+use matrix_sdk::ruma::{OwnedUserId, UserId};
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::error;
+
+use crate::state_updaters::{ROOM_STORE_ID, ROOMS_COLLECTION_STORE_ID, patch_store};
+use crate::utils::{get_client, wait_for_client};
+
+/// The users ignored through `m.ignored_user_list`, hidden from the room and rooms-collection stores.
+#[derive(Default)]
+pub struct IgnoredUsers {
+    users: Mutex<HashSet<String>>,
+    /// The last stores sent to the frontend, before filtering,
+    /// so they can be filtered again when the list changes.
+    last_room_screen: Mutex<Option<Value>>,
+    last_rooms_list: Mutex<Option<Value>>,
+}
+
+impl IgnoredUsers {
+    fn is_ignored(&self, user_id: Option<&str>) -> bool {
+        user_id.is_some_and(|id| {
+            self.users
+                .lock()
+                .expect("Failed to lock mutex")
+                .contains(id)
+        })
+    }
+
+    fn filter_room_screen_in_place(&self, room_screen: &mut Value) {
+        if let Some(items) = room_screen
+            .pointer_mut("/tlState/items")
+            .and_then(Value::as_array_mut)
+        {
+            items.retain(|item| {
+                !self.is_ignored(item.pointer("/data/senderId").and_then(Value::as_str))
+            });
+        }
+    }
+
+    fn filter_rooms_list_in_place(&self, rooms_list: &mut Value) {
+        let mut hidden = Vec::new();
+        if let Some(invites) = rooms_list
+            .get_mut("invitedRooms")
+            .and_then(Value::as_object_mut)
+        {
+            invites.retain(|room_id, invite| {
+                let ignored = self.is_ignored(
+                    invite
+                        .pointer("/inviterInfo/userId")
+                        .and_then(Value::as_str),
+                );
+                if ignored {
+                    hidden.push(room_id.clone());
+                }
+                !ignored
+            });
+        }
+        if let Some(displayed) = rooms_list
+            .get_mut("displayedInvitedRooms")
+            .and_then(Value::as_array_mut)
+        {
+            displayed.retain(|id| !id.as_str().is_some_and(|id| hidden.iter().any(|h| h == id)));
+        }
+    }
+
+    /// Remove the timeline items sent by ignored users from a serialized `RoomScreen`.
+    pub fn filter_room_screen(&self, room_screen: &mut Value) {
+        *self.last_room_screen.lock().expect("Failed to lock mutex") = Some(room_screen.clone());
+        self.filter_room_screen_in_place(room_screen);
+    }
+
+    /// Remove the invites sent by ignored users from a serialized `RoomsList`.
+    pub fn filter_rooms_list(&self, rooms_list: &mut Value) {
+        *self.last_rooms_list.lock().expect("Failed to lock mutex") = Some(rooms_list.clone());
+        self.filter_rooms_list_in_place(rooms_list);
+    }
+
+    /// Replace the ignored users, then filter the last stores again.
+    /// Only the filtered fields are pushed, so the other fields of the stores, which
+    /// may have been updated since (e.g. the trust level of members), are kept.
+    fn set_users<R: Runtime>(&self, app_handle: &AppHandle<R>, users: HashSet<String>) {
+        {
+            let mut current = self.users.lock().expect("Failed to lock mutex");
+            if *current == users {
+                return;
+            }
+            *current = users;
+        }
+
+        let last_room_screen = self
+            .last_room_screen
+            .lock()
+            .expect("Failed to lock mutex")
+            .clone();
+        if let Some(mut room_screen) = last_room_screen {
+            self.filter_room_screen_in_place(&mut room_screen);
+            let filtered = only_fields(room_screen, &["tlState"]);
+            if let Err(e) = patch_store(app_handle, ROOM_STORE_ID, filtered) {
+                error!("Couldn't hide ignored users from the room store. {e}");
+            }
+        }
+        let last_rooms_list = self
+            .last_rooms_list
+            .lock()
+            .expect("Failed to lock mutex")
+            .clone();
+        if let Some(mut rooms_list) = last_rooms_list {
+            self.filter_rooms_list_in_place(&mut rooms_list);
+            let filtered = only_fields(rooms_list, &["invitedRooms", "displayedInvitedRooms"]);
+            if let Err(e) = patch_store(app_handle, ROOMS_COLLECTION_STORE_ID, filtered) {
+                error!("Couldn't hide ignored users from the rooms collection store. {e}");
+            }
+        }
+    }
+}
+
+/// Keep the given fields of a serialized store, so it can be patched with them only.
+fn only_fields(store: Value, fields: &[&str]) -> Value {
+    let Value::Object(mut store) = store else {
+        return store;
+    };
+    Value::Object(
+        fields
+            .iter()
+            .filter_map(|field| Some((field.to_string(), store.remove(*field)?)))
+            .collect::<Map<String, Value>>(),
+    )
+}
+
+fn user_ids(content: &IgnoredUserListEventContent) -> HashSet<String> {
+    content
+        .ignored_users
+        .keys()
+        .map(ToString::to_string)
+        .collect()
+}
+
+pub async fn get_ignored_users() -> anyhow::Result<Vec<OwnedUserId>> {
+    let content = get_client()?
+        .account()
+        .account_data::<IgnoredUserListEventContent>()
+        .await?
+        .map(|raw| raw.deserialize())
+        .transpose()?;
+    Ok(content
+        .map(|c| c.ignored_users.into_keys().collect())
+        .unwrap_or_default())
+}
+
+/// Ignore a user. Their messages and invites are hidden right away.
+pub async fn ignore_user<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    user_id: &UserId,
+) -> anyhow::Result<()> {
+    get_client()?.account().ignore_user(user_id).await?;
+    update_users(app_handle, |users| {
+        users.insert(user_id.to_string());
+    });
+    Ok(())
+}
+
+pub async fn unignore_user<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    user_id: &UserId,
+) -> anyhow::Result<()> {
+    get_client()?.account().unignore_user(user_id).await?;
+    update_users(app_handle, |users| {
+        users.remove(user_id.as_str());
+    });
+    Ok(())
+}
+
+/// Apply a change without waiting for the account data to come back from the sync.
+fn update_users<R: Runtime>(app_handle: &AppHandle<R>, change: impl FnOnce(&mut HashSet<String>)) {
+    let ignored = app_handle.state::<IgnoredUsers>();
+    let mut users = ignored.users.lock().expect("Failed to lock mutex").clone();
+    change(&mut users);
+    ignored.set_users(app_handle, users);
+}
+
+/// Keep the ignored users in sync with the account data. It is started once in the plugin setup.
+pub async fn run_ignored_users_listener<R: Runtime>(app_handle: AppHandle<R>) {
+    let client = wait_for_client().await;
+
+    match client
+        .account()
+        .account_data::<IgnoredUserListEventContent>()
+        .await
+        .map(|raw| raw.map(|raw| raw.deserialize()))
+    {
+        Ok(Some(Ok(content))) => app_handle
+            .state::<IgnoredUsers>()
+            .set_users(&app_handle, user_ids(&content)),
+        Ok(Some(Err(e))) => error!("Couldn't deserialize the ignored users. {e}"),
+        Ok(None) => {}
+        Err(e) => error!("Couldn't get the ignored users. {e}"),
+    }
+
+    client.add_event_handler(
+        move |event: GlobalAccountDataEvent<IgnoredUserListEventContent>| {
+            let app_handle = app_handle.clone();
+            async move {
+                app_handle
+                    .state::<IgnoredUsers>()
+                    .set_users(&app_handle, user_ids(&event.content));
+            }
+        },
+    );
+}
@@ -0,0 +1,203 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::anyhow;
+use matrix_sdk::ruma::api::client::membership::leave_room;
+use matrix_sdk::ruma::events::room::member::{MembershipState, StrippedRoomMemberEvent};
+use matrix_sdk::ruma::{OwnedRoomId, OwnedUserId, RoomId, ServerName, UserId};
+use matrix_sdk::{Client, Room, RoomState};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::{error, info, warn};
+
+use crate::utils::{get_client, get_room, wait_for_client};
+
+const POLICY_FILE_NAME: &str = "invite_policy.json";
+
+/// How incoming invites are handled before reaching the invites list.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvitePolicy {
+    /// Decline the invites from users who don't share any joined room with us.
+    /// The invites already pending when the app starts are kept.
+    pub decline_from_strangers: bool,
+}
+
+/// The invite policy, persisted in the app data directory.
+pub struct InvitePolicyState {
+    dir: PathBuf,
+    policy: Mutex<InvitePolicy>,
+    /// The invites pending once the sync service first ran, which the policy leaves alone.
+    pending_at_startup: OnceLock<HashSet<OwnedRoomId>>,
+}
+
+impl InvitePolicyState {
+    pub fn load(app_data_dir: PathBuf) -> Self {
+        let policy = match std::fs::read_to_string(app_data_dir.join(POLICY_FILE_NAME)) {
+            Ok(raw) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+                error!("Couldn't deserialize invite policy. {e}");
+                InvitePolicy::default()
+            }),
+            Err(_) => InvitePolicy::default(),
+        };
+        Self {
+            dir: app_data_dir,
+            policy: Mutex::new(policy),
+            pending_at_startup: OnceLock::new(),
+        }
+    }
+
+    pub fn get(&self) -> InvitePolicy {
+        self.policy.lock().expect("Failed to lock mutex").clone()
+    }
+
+    pub fn set(&self, policy: InvitePolicy) -> anyhow::Result<()> {
+        std::fs::write(
+            self.dir.join(POLICY_FILE_NAME),
+            serde_json::to_string(&policy)?,
+        )?;
+        *self.policy.lock().expect("Failed to lock mutex") = policy;
+        Ok(())
+    }
+}
+
+fn get_invited_room(room_id: &RoomId) -> anyhow::Result<Room> {
+    let room = get_room(room_id)?;
+    if room.state() != RoomState::Invited {
+        return Err(anyhow!("You are not invited to room {room_id}"));
+    }
+    Ok(room)
+}
+
+async fn get_inviter(room: &Room) -> anyhow::Result<Option<OwnedUserId>> {
+    Ok(room
+        .invite_details()
+        .await?
+        .inviter
+        .map(|inviter| inviter.user_id().to_owned()))
+}
+
+pub async fn accept_invite(room_id: &RoomId) -> anyhow::Result<()> {
+    get_invited_room(room_id)?.join().await?;
+    Ok(())
+}
+
+/// Decline an invite, with an optional reason shown to the inviter.
+pub async fn decline_invite(room_id: &RoomId, reason: Option<String>) -> anyhow::Result<()> {
+    get_invited_room(room_id)?;
+    let mut request = leave_room::v3::Request::new(room_id.to_owned());
+    request.reason = reason.filter(|r| !r.is_empty());
+    get_client()?.send(request).await?;
+    Ok(())
+}
+
+/// Decline an invite, then add the inviter to the ignored users.
+/// Returns the ignored user, if the inviter is known.
+pub async fn decline_invite_and_ignore_inviter(
+    room_id: &RoomId,
+    reason: Option<String>,
+) -> anyhow::Result<Option<OwnedUserId>> {
+    let inviter = get_inviter(&get_invited_room(room_id)?).await?;
+    decline_invite(room_id, reason).await?;
+    if let Some(inviter) = &inviter {
+        get_client()?.account().ignore_user(inviter).await?;
+    }
+    Ok(inviter)
+}
+
+/// Decline every pending invite sent by users of the given homeserver.
+/// Returns the declined rooms.
+pub async fn decline_invites_from_server(
+    server_name: &ServerName,
+    reason: Option<String>,
+) -> anyhow::Result<Vec<OwnedRoomId>> {
+    let mut declined = Vec::new();
+    for room in get_client()?.invited_rooms() {
+        let Some(inviter) = get_inviter(&room).await? else {
+            continue;
+        };
+        if inviter.server_name() != server_name {
+            continue;
+        }
+        match decline_invite(room.room_id(), reason.clone()).await {
+            Ok(()) => declined.push(room.room_id().to_owned()),
+            Err(e) => warn!("Couldn't decline invite to {}. {e}", room.room_id()),
+        }
+    }
+    Ok(declined)
+}
+
+/// Whether we share a joined room with the given user.
+/// The members of a room are fetched if they haven't been synced yet.
+async fn shares_a_room(client: &Client, user_id: &UserId) -> bool {
+    for room in client.joined_rooms() {
+        match room.get_member(user_id).await {
+            Ok(Some(member)) if *member.membership() == MembershipState::Join => return true,
+            Ok(_) => {}
+            Err(e) => warn!("Couldn't get the members of {}. {e}", room.room_id()),
+        }
+    }
+    false
+}
+
+async fn on_stripped_member<R: Runtime>(
+    app_handle: AppHandle<R>,
+    client: Client,
+    event: StrippedRoomMemberEvent,
+    room: Room,
+) {
+    if event.content.membership != MembershipState::Invite
+        || client.user_id() != Some(&*event.state_key)
+    {
+        return;
+    }
+    let state = app_handle.state::<InvitePolicyState>();
+    // Before the first sync, the invites come from the account, they aren't new.
+    let Some(pending_at_startup) = state.pending_at_startup.get() else {
+        return;
+    };
+    if pending_at_startup.contains(room.room_id())
+        || !state.get().decline_from_strangers
+        || shares_a_room(&client, &event.sender).await
+    {
+        return;
+    }
+    info!(
+        "Declining invite to {} from {}, who shares no room with us",
+        room.room_id(),
+        event.sender
+    );
+    if let Err(e) = room.leave().await {
+        error!("Couldn't decline invite to {}. {e}", room.room_id());
+    }
+}
+
+/// Apply the invite policy to the incoming invites. It is started once in the plugin setup.
+/// The invites already pending when the sync service first runs are left to the user.
+pub async fn run_invite_filter<R: Runtime>(app_handle: AppHandle<R>) {
+    let client = wait_for_client().await;
+    client.add_event_handler(
+        move |event: StrippedRoomMemberEvent, room: Room, client: Client| {
+            on_stripped_member(app_handle.clone(), client, event, room)
+        },
+    );
+}
+
+/// Remember the pending invites the first time the sync service runs, so that a fresh login
+/// or a cleared cache doesn't decline the invites already in the account.
+pub fn on_sync_service_running<R: Runtime>(app_handle: &AppHandle<R>) {
+    let Ok(client) = get_client() else {
+        return;
+    };
+    app_handle
+        .state::<InvitePolicyState>()
+        .pending_at_startup
+        .get_or_init(|| {
+            client
+                .invited_rooms()
+                .iter()
+                .map(|room| room.room_id().to_owned())
+                .collect()
+        });
+}
@@ -12,14 +12,40 @@ mod desktop;
 #[cfg(mobile)]
 mod mobile;
 
+mod account;
+mod autocomplete;
 mod commands;
+mod create_room;
+mod cross_signing;
+mod devices;
+mod direct_messages;
+mod edit_history;
 mod error;
 mod events;
+mod formatting;
+mod forward;
+mod ignored_users;
+mod invites;
 mod keyring;
+#[cfg(desktop)]
+mod loopback;
+mod outbox;
+mod power_levels;
+mod presence;
+mod qr_login;
+mod receipts;
+mod registration;
+mod reporting;
+mod room_settings;
+mod room_upgrade;
+mod scheduled_messages;
+mod slash_commands;
 mod state_updaters;
+mod uia;
+mod user_verification;
 mod utils;
 
-pub use error::{Error, Result};
+pub use error::{Endpoint, Error, Result};
 
 #[cfg(desktop)]
 use desktop::MatrixSvelte;
@@ -29,8 +55,19 @@ use tracing::{debug, error, info};
 use url::Url;
 
 use crate::{
+    autocomplete::AutocompleteCache,
     events::handle_incoming_events,
+    ignored_users::IgnoredUsers,
+    invites::InvitePolicyState,
+    outbox::Outbox,
+    power_levels::RolePresets,
+    presence::PresenceTracker,
+    qr_login::QrLoginState,
+    registration::RegistrationClient,
+    scheduled_messages::ScheduledMessagesQueue,
     state_updaters::Updaters,
+    uia::UiaSessions,
+    user_verification::trust::MemberTrust,
     utils::{get_app_dir_or_create_it, get_plugin_config},
 };
 
@@ -48,6 +85,8 @@ pub struct PluginConfig {
     /// The client URL for the OAuth flow
     pub oauth_client_uri: Url,
     /// The redirect URI called at the end of the OAuth flow.
+    /// On desktop, a loopback URI like `http://127.0.0.1:8080/callback` is captured by the plugin,
+    /// so no deep link needs to be registered.
     pub oauth_redirect_uri: Url,
 }
 
@@ -102,7 +141,79 @@ pub fn init<R: Runtime>() -> TauriPlugin<R, PluginConfig> {
             commands::get_matrix_to_permalink_for_room,
             #[cfg(target_os = "android")]
             commands::android_share_matrix_media,
-            commands::register_notifications
+            commands::register_notifications,
+            commands::schedule_text_message,
+            commands::schedule_media_message,
+            commands::get_scheduled_messages,
+            commands::edit_scheduled_message,
+            commands::cancel_scheduled_message,
+            commands::retry_send,
+            commands::cancel_send,
+            commands::retry_all_failed,
+            commands::get_edit_history,
+            commands::get_event_receipts_and_reactions,
+            commands::forward_event,
+            commands::format_markdown,
+            commands::execute_composer_input,
+            commands::autocomplete,
+            commands::get_room_power_levels,
+            commands::set_user_power_level,
+            commands::set_power_level_threshold,
+            commands::get_role_presets,
+            commands::save_role_preset,
+            commands::delete_role_preset,
+            commands::get_room_settings,
+            commands::update_room_settings,
+            commands::enable_room_encryption,
+            commands::get_supported_room_versions,
+            commands::upgrade_room,
+            commands::follow_tombstone,
+            commands::get_room_predecessor,
+            commands::create_room,
+            commands::get_or_create_group_dm,
+            commands::merge_duplicate_direct_entries,
+            commands::accept_invite,
+            commands::decline_invite,
+            commands::decline_invite_and_ignore_inviter,
+            commands::decline_invites_from_server,
+            commands::get_invite_policy,
+            commands::set_invite_policy,
+            commands::get_ignored_users,
+            commands::ignore_user,
+            commands::unignore_user,
+            commands::report_event,
+            commands::report_room,
+            commands::report_user,
+            commands::set_own_presence,
+            commands::report_user_activity,
+            commands::set_idle_timeout,
+            commands::change_password,
+            commands::get_third_party_ids,
+            commands::request_third_party_token,
+            commands::add_third_party_id,
+            commands::delete_third_party_id,
+            commands::deactivate_account,
+            commands::continue_uia,
+            commands::cancel_uia,
+            commands::get_uia_fallback_url,
+            commands::rename_device,
+            commands::delete_devices,
+            commands::sign_out_other_devices,
+            commands::get_stale_devices,
+            commands::get_registration_flows,
+            commands::check_username_availability,
+            commands::check_registration_token,
+            commands::request_registration_email_token,
+            commands::register_account,
+            commands::grant_qr_login,
+            commands::submit_qr_login_check_code,
+            commands::request_user_verification,
+            commands::accept_user_verification,
+            commands::start_sas_verification,
+            commands::get_user_verification_qr_code,
+            commands::scan_user_verification_qr_code,
+            commands::confirm_user_verification,
+            commands::cancel_user_verification
         ])
         .setup(|app, api| {
             let init_app_handle = app.app_handle().clone();
@@ -130,6 +241,54 @@ pub fn init<R: Runtime>() -> TauriPlugin<R, PluginConfig> {
                 }
             }
 
+            // Scheduled messages queue, persisted across restarts
+            app.manage(ScheduledMessagesQueue::load(app_data_dir.clone()));
+            tauri::async_runtime::spawn(scheduled_messages::run_scheduler(
+                app.app_handle().clone(),
+            ));
+
+            // Send queue of all rooms
+            app.manage(Outbox::default());
+            tauri::async_runtime::spawn(outbox::run_outbox_listener(app.app_handle().clone()));
+
+            // Custom role presets, persisted across restarts
+            app.manage(RolePresets::load(app_data_dir.clone()));
+
+            // Room members and powers, used by the composer autocomplete
+            app.manage(AutocompleteCache::default());
+
+            // Policy applied to incoming invites, persisted across restarts
+            app.manage(InvitePolicyState::load(app_data_dir.clone()));
+            tauri::async_runtime::spawn(invites::run_invite_filter(app.app_handle().clone()));
+
+            // Users hidden from the stores, kept in sync with the account data
+            app.manage(IgnoredUsers::default());
+            tauri::async_runtime::spawn(ignored_users::run_ignored_users_listener(
+                app.app_handle().clone(),
+            ));
+
+            // Own presence with idle detection, and presence of the displayed users
+            app.manage(PresenceTracker::default());
+            tauri::async_runtime::spawn(presence::run_presence_loop(app.app_handle().clone()));
+
+            // Operations waiting for user-interactive authentication
+            app.manage(UiaSessions::default());
+
+            // Unauthenticated client used to register a new account
+            app.manage(RegistrationClient::default());
+
+            // Login granted to a new device through a QR code
+            app.manage(QrLoginState::default());
+
+            // Verification of other users, and trust level of the room members
+            tauri::async_runtime::spawn(user_verification::run_verification_request_listener(
+                app.app_handle().clone(),
+            ));
+            app.manage(MemberTrust::default());
+            tauri::async_runtime::spawn(user_verification::trust::run_member_trust_listener(
+                app.app_handle().clone(),
+            ));
+
             let forwarder_handle = app.app_handle().clone();
 
             let _monitor = tauri::async_runtime::spawn(async move {
@@ -0,0 +1,133 @@
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use tauri::{AppHandle, Runtime};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tracing::{error, info};
+use url::Url;
+
+use crate::AUTH_DEEPLINK_SENDER;
+use crate::utils::get_plugin_config;
+
+/// The user may take a while to log in, but the port shouldn't stay bound forever.
+const REDIRECT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const RESPONSE_PAGE: &str = "<!DOCTYPE html><html><head><meta charset=\"utf-8\"></head>\
+    <body><p>You are logged in, you can close this page and go back to the app.</p></body></html>";
+
+static IS_LISTENING_FOR_OAUTH: AtomicBool = AtomicBool::new(false);
+
+/// Whether a redirect URL can be captured by a loopback listener, i.e. `http://127.0.0.1:8080/`.
+pub fn is_loopback_url(url: &Url) -> bool {
+    url.scheme() == "http" && url.host_str() == Some("127.0.0.1") && url.port().is_some()
+}
+
+/// A one-shot HTTP listener on `127.0.0.1`, capturing the redirect at the end of a login
+/// done in the system browser.
+pub struct LoopbackListener {
+    listener: TcpListener,
+    redirect_url: Url,
+}
+
+impl LoopbackListener {
+    /// Listen on the port of a loopback redirect URL.
+    pub async fn bind(redirect_url: Url) -> anyhow::Result<Self> {
+        if !is_loopback_url(&redirect_url) {
+            return Err(anyhow!("{redirect_url} isn't a loopback URL"));
+        }
+        let port = redirect_url.port().expect("loopback URLs have a port");
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
+        Ok(Self {
+            listener,
+            redirect_url,
+        })
+    }
+
+    /// Wait for the browser to be redirected to the listened path, and return the full
+    /// redirect URL with its query.
+    pub async fn wait_for_redirect(self) -> anyhow::Result<Url> {
+        tokio::time::timeout(REDIRECT_TIMEOUT, self.accept_redirect())
+            .await
+            .map_err(|_| anyhow!("Timed out waiting for the login redirect"))?
+    }
+
+    async fn accept_redirect(&self) -> anyhow::Result<Url> {
+        loop {
+            let (mut stream, _) = self.listener.accept().await?;
+            let (read, mut write) = stream.split();
+            // i.e. `GET /callback?code=...&state=... HTTP/1.1`
+            let mut request_line = String::new();
+            BufReader::new(read).read_line(&mut request_line).await?;
+            let Some(url) = request_line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|target| self.redirect_url.join(target).ok())
+            else {
+                write
+                    .write_all(
+                        b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    )
+                    .await?;
+                continue;
+            };
+
+            // Browsers also ask for the favicon.
+            if url.path() != self.redirect_url.path() {
+                write
+                    .write_all(
+                        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    )
+                    .await?;
+                continue;
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{RESPONSE_PAGE}",
+                RESPONSE_PAGE.len()
+            );
+            write.write_all(response.as_bytes()).await?;
+            return Ok(url);
+        }
+    }
+}
+
+/// When the OAuth redirect URI is a loopback URL, capture the redirect and forward it like
+/// `forward_oauth_login_deeplink` does, so the app doesn't need to register a deep link.
+/// It is called each time an OAuth URL is emitted.
+pub fn listen_for_oauth_redirect<R: Runtime>(app_handle: &AppHandle<R>) {
+    let redirect_uri = match get_plugin_config(app_handle) {
+        Ok(config) => config.oauth_redirect_uri,
+        Err(e) => {
+            error!("Couldn't get the OAuth redirect URI. {e}");
+            return;
+        }
+    };
+    if !is_loopback_url(&redirect_uri) {
+        return;
+    }
+    // A previous login attempt may still be waiting on the same port.
+    if IS_LISTENING_FOR_OAUTH.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = forward_oauth_redirect(redirect_uri).await {
+            error!("Couldn't capture the OAuth redirect. {e}");
+        }
+        IS_LISTENING_FOR_OAUTH.store(false, Ordering::SeqCst);
+    });
+}
+
+async fn forward_oauth_redirect(redirect_uri: Url) -> anyhow::Result<()> {
+    let url = LoopbackListener::bind(redirect_uri)
+        .await?
+        .wait_for_redirect()
+        .await?;
+    info!("OAuth redirect captured by the loopback listener");
+    AUTH_DEEPLINK_SENDER
+        .get()
+        .ok_or(anyhow!("The OAuth redirect receiver isn't ready yet"))?
+        .send(url)
+        .await
+        .map_err(|_| anyhow!("Couldn't forward the OAuth redirect"))
+}
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use matrix_sdk::ruma::events::AnyMessageLikeEventContent;
+use matrix_sdk::ruma::{OwnedRoomId, OwnedTransactionId, TransactionId};
+use matrix_sdk::send_queue::{
+    LocalEcho, LocalEchoContent, RoomSendQueueUpdate, SendHandle, SendQueueUpdate,
+};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Listener, Manager, Runtime};
+use tauri_plugin_svelte::ManagerExt;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, warn};
+
+use crate::state_updaters::ROOM_STORE_ID;
+use crate::utils::{get_client, wait_for_client};
+
+/// A local echo that hasn't been acknowledged by the homeserver yet.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboxItem {
+    pub transaction_id: OwnedTransactionId,
+    /// Plain text body of the message, if any.
+    pub body: Option<String>,
+    pub state: OutboxItemState,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "state"
+)]
+pub enum OutboxItemState {
+    Pending,
+    Sending,
+    /// A recoverable failure (i.e. network) is retried automatically
+    /// once the sync service is running again.
+    Failed {
+        reason: String,
+        is_recoverable: bool,
+    },
+}
+
+struct OutboxEntry {
+    item: OutboxItem,
+    handle: Option<SendHandle>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ActiveRoomPayload {
+    room_id: OwnedRoomId,
+}
+
+/// Tracks the send queue of every room, and mirrors the one of the
+/// currently opened room into the room store.
+#[derive(Default)]
+pub struct Outbox {
+    rooms: Mutex<HashMap<OwnedRoomId, Vec<OutboxEntry>>>,
+    current_room: Mutex<Option<OwnedRoomId>>,
+}
+
+impl Outbox {
+    fn mirror<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        let Some(room_id) = self
+            .current_room
+            .lock()
+            .expect("Failed to lock mutex")
+            .clone()
+        else {
+            return;
+        };
+        let items: Vec<OutboxItem> = self
+            .rooms
+            .lock()
+            .expect("Failed to lock mutex")
+            .get(&room_id)
+            .map(|entries| entries.iter().map(|e| e.item.clone()).collect())
+            .unwrap_or_default();
+        let value = serde_json::to_value(items).expect("Couldn't serialize send queue");
+        if let Err(e) = app_handle.svelte().set(ROOM_STORE_ID, "sendQueue", value) {
+            error!("Couldn't mirror send queue to the room store. {e}");
+        }
+    }
+
+    fn set_room_echoes(&self, room_id: OwnedRoomId, echoes: Vec<LocalEcho>) {
+        let entries = echoes
+            .into_iter()
+            .filter_map(entry_from_local_echo)
+            .collect();
+        self.rooms
+            .lock()
+            .expect("Failed to lock mutex")
+            .insert(room_id, entries);
+    }
+
+    fn apply_update(&self, update: SendQueueUpdate) {
+        let mut rooms = self.rooms.lock().expect("Failed to lock mutex");
+        let entries = rooms.entry(update.room_id).or_default();
+        match update.update {
+            RoomSendQueueUpdate::NewLocalEvent(echo) => {
+                if let Some(entry) = entry_from_local_echo(echo) {
+                    entries.push(entry);
+                }
+            }
+            RoomSendQueueUpdate::CancelledLocalEvent { transaction_id }
+            | RoomSendQueueUpdate::SentEvent { transaction_id, .. } => {
+                entries.retain(|e| e.item.transaction_id != transaction_id);
+            }
+            RoomSendQueueUpdate::RetryEvent { transaction_id } => {
+                if let Some(state) = state_mut(entries, &transaction_id) {
+                    *state = OutboxItemState::Sending;
+                }
+            }
+            RoomSendQueueUpdate::SendError {
+                transaction_id,
+                error,
+                is_recoverable,
+            } => {
+                if let Some(state) = state_mut(entries, &transaction_id) {
+                    *state = OutboxItemState::Failed {
+                        reason: error.to_string(),
+                        is_recoverable,
+                    };
+                }
+            }
+            RoomSendQueueUpdate::MediaUpload { related_to, .. } => {
+                if let Some(state) = state_mut(entries, &related_to) {
+                    *state = OutboxItemState::Sending;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle(
+        &self,
+        room_id: &OwnedRoomId,
+        transaction_id: &TransactionId,
+    ) -> anyhow::Result<SendHandle> {
+        self.rooms
+            .lock()
+            .expect("Failed to lock mutex")
+            .get(room_id)
+            .and_then(|entries| {
+                entries
+                    .iter()
+                    .find(|e| e.item.transaction_id == transaction_id)
+            })
+            .and_then(|e| e.handle.clone())
+            .ok_or(anyhow!("No local echo {transaction_id} in room {room_id}"))
+    }
+
+    fn failed_handles(&self) -> Vec<SendHandle> {
+        self.rooms
+            .lock()
+            .expect("Failed to lock mutex")
+            .values()
+            .flatten()
+            .filter(|e| matches!(e.item.state, OutboxItemState::Failed { .. }))
+            .filter_map(|e| e.handle.clone())
+            .collect()
+    }
+
+    /// Retry a single failed local echo.
+    pub async fn retry_send(
+        &self,
+        room_id: &OwnedRoomId,
+        transaction_id: &TransactionId,
+    ) -> anyhow::Result<()> {
+        let handle = self.handle(room_id, transaction_id)?;
+        handle.unwedge().await?;
+        // A recoverable error disables the room queue, so we re-enable it as well.
+        get_client()?
+            .get_room(room_id)
+            .ok_or(anyhow!("Room {room_id} not found"))?
+            .send_queue()
+            .set_enabled(true);
+        Ok(())
+    }
+
+    /// Cancel a local echo that hasn't been sent yet.
+    pub async fn cancel_send(
+        &self,
+        room_id: &OwnedRoomId,
+        transaction_id: &TransactionId,
+    ) -> anyhow::Result<()> {
+        let handle = self.handle(room_id, transaction_id)?;
+        if !handle.abort().await? {
+            return Err(anyhow!("This event has already been sent"));
+        }
+        Ok(())
+    }
+
+    /// Retry every failed local echo in every room.
+    pub async fn retry_all_failed(&self) -> anyhow::Result<()> {
+        for handle in self.failed_handles() {
+            if let Err(e) = handle.unwedge().await {
+                warn!("Couldn't unwedge local echo. {e}");
+            }
+        }
+        self.retry_recoverable().await
+    }
+
+    /// Re-enable the send queues disabled by a recoverable (i.e. network) error.
+    async fn retry_recoverable(&self) -> anyhow::Result<()> {
+        {
+            let mut rooms = self.rooms.lock().expect("Failed to lock mutex");
+            for entry in rooms.values_mut().flatten() {
+                if let OutboxItemState::Failed {
+                    is_recoverable: true,
+                    ..
+                } = entry.item.state
+                {
+                    entry.item.state = OutboxItemState::Pending;
+                }
+            }
+        }
+        get_client()?.send_queue().set_enabled(true).await;
+        Ok(())
+    }
+}
+
+fn state_mut<'a>(
+    entries: &'a mut [OutboxEntry],
+    transaction_id: &TransactionId,
+) -> Option<&'a mut OutboxItemState> {
+    entries
+        .iter_mut()
+        .find(|e| e.item.transaction_id == transaction_id)
+        .map(|e| &mut e.item.state)
+}
+
+fn entry_from_local_echo(echo: LocalEcho) -> Option<OutboxEntry> {
+    // Reactions are displayed on their target event, not in the send queue.
+    let LocalEchoContent::Event {
+        serialized_event,
+        send_handle,
+        send_error,
+    } = echo.content
+    else {
+        return None;
+    };
+    let body = match serialized_event.deserialize() {
+        Ok(AnyMessageLikeEventContent::RoomMessage(content)) => Some(content.body().to_owned()),
+        _ => None,
+    };
+    let state = match send_error {
+        Some(e) => OutboxItemState::Failed {
+            reason: e.to_string(),
+            is_recoverable: false,
+        },
+        None => OutboxItemState::Pending,
+    };
+    Some(OutboxEntry {
+        item: OutboxItem {
+            transaction_id: echo.transaction_id,
+            body,
+            state,
+        },
+        handle: Some(send_handle),
+    })
+}
+
+async fn load_room_echoes<R: Runtime>(app_handle: AppHandle<R>, room_id: OwnedRoomId) {
+    let outbox = app_handle.state::<Outbox>();
+    let Some(room) = wait_for_client().await.get_room(&room_id) else {
+        return;
+    };
+    match room.send_queue().subscribe().await {
+        Ok((echoes, _)) => outbox.set_room_echoes(room_id, echoes),
+        Err(e) => error!("Couldn't get local echoes of room {room_id}. {e}"),
+    }
+    outbox.mirror(&app_handle);
+}
+
+/// Listen to all send queue updates. It is started once in the plugin setup.
+pub async fn run_outbox_listener<R: Runtime>(app_handle: AppHandle<R>) {
+    let listener_handle = app_handle.clone();
+    app_handle.listen("matrix-svelte://update-current-active-room", move |e| {
+        if let Ok(payload) = serde_json::from_str::<ActiveRoomPayload>(e.payload()) {
+            *listener_handle
+                .state::<Outbox>()
+                .current_room
+                .lock()
+                .expect("Failed to lock mutex") = Some(payload.room_id.clone());
+            tauri::async_runtime::spawn(load_room_echoes(listener_handle.clone(), payload.room_id));
+        }
+    });
+
+    let client = wait_for_client().await;
+    let mut updates = client.send_queue().subscribe();
+    let outbox = app_handle.state::<Outbox>();
+    loop {
+        match updates.recv().await {
+            Ok(update) => {
+                outbox.apply_update(update);
+                outbox.mirror(&app_handle);
+            }
+            Err(RecvError::Lagged(missed)) => {
+                warn!("Send queue listener lagged behind, {missed} updates missed");
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Automatically retry the sends that failed because we were offline,
+/// once the sync service is running again.
+pub fn on_sync_service_running<R: Runtime>(app_handle: &AppHandle<R>) {
+    let handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        let outbox = handle.state::<Outbox>();
+        if let Err(e) = outbox.retry_recoverable().await {
+            warn!("Couldn't retry failed sends. {e}");
+        }
+        outbox.mirror(&handle);
+    });
+}
@@ -0,0 +1,340 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use matrix_sdk::ruma::events::room::power_levels::{
+    RoomPowerLevels, RoomPowerLevelsEventContent, UserPowerLevel,
+};
+use matrix_sdk::ruma::events::{StateEventType, TimelineEventType};
+use matrix_sdk::ruma::{Int, RoomId, UserId};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::utils::{get_room, write_atomically};
+
+const PRESETS_FILE_NAME: &str = "role_presets.json";
+/// The level from which a user is considered as an administrator.
+const ADMIN_LEVEL: i32 = 100;
+const MODERATOR_LEVEL: i32 = 50;
+
+/// The full power levels of a room, along with the level of the current user.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomPowerLevelsInfo {
+    /// The `m.room.power_levels` content, as defined in the spec.
+    pub content: RoomPowerLevelsEventContent,
+    /// `None` for room creators, who have an infinite power level since room version 12.
+    pub own_level: Option<Int>,
+    pub can_edit: bool,
+}
+
+/// A threshold of the `m.room.power_levels` content.
+#[derive(Debug, Deserialize)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "kind"
+)]
+pub enum PowerLevelThreshold {
+    Ban,
+    Kick,
+    Invite,
+    Redact,
+    EventsDefault,
+    StateDefault,
+    UsersDefault,
+    NotifyRoom,
+    /// The level required to send a given event type.
+    /// Setting it to `None` falls back to the events or state default.
+    Event {
+        event_type: String,
+    },
+}
+
+/// A named power level, used to pick a role for a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RolePreset {
+    pub name: String,
+    pub level: i64,
+    /// Built-in presets (admin and moderator) can't be changed.
+    #[serde(default)]
+    pub built_in: bool,
+}
+
+fn level_value(level: UserPowerLevel) -> Option<Int> {
+    match level {
+        UserPowerLevel::Int(level) => Some(level),
+        _ => None,
+    }
+}
+
+pub async fn get_room_power_levels(room_id: &RoomId) -> anyhow::Result<RoomPowerLevelsInfo> {
+    let room = get_room(room_id)?;
+    let power_levels = room.power_levels().await?;
+    let own_user_id = room.own_user_id();
+    Ok(RoomPowerLevelsInfo {
+        own_level: level_value(power_levels.for_user(own_user_id)),
+        can_edit: power_levels.user_can_send_state(own_user_id, StateEventType::RoomPowerLevels),
+        content: RoomPowerLevelsEventContent::try_from(power_levels)?,
+    })
+}
+
+fn ensure_can_edit(power_levels: &RoomPowerLevels, own_user_id: &UserId) -> anyhow::Result<()> {
+    if !power_levels.user_can_send_state(own_user_id, StateEventType::RoomPowerLevels) {
+        return Err(anyhow!(
+            "You are not allowed to change the power levels of this room"
+        ));
+    }
+    Ok(())
+}
+
+/// Ensure the current user can set a level, which must not be higher than their own.
+fn ensure_can_grant(own_level: Option<Int>, level: Int) -> anyhow::Result<()> {
+    if own_level.is_some_and(|own| level > own) {
+        return Err(anyhow!("You can't grant a level higher than your own"));
+    }
+    Ok(())
+}
+
+pub async fn set_user_power_level(
+    room_id: &RoomId,
+    user_id: &UserId,
+    level: i64,
+) -> anyhow::Result<()> {
+    let room = get_room(room_id)?;
+    let power_levels = room.power_levels().await?;
+    let own_user_id = room.own_user_id();
+    ensure_can_edit(&power_levels, own_user_id)?;
+
+    let level = Int::try_from(level)?;
+    let own_level = level_value(power_levels.for_user(own_user_id));
+    ensure_can_grant(own_level, level)?;
+
+    let Some(current_level) = level_value(power_levels.for_user(user_id)) else {
+        return Err(anyhow!("The level of a room creator can't be changed"));
+    };
+    if user_id != own_user_id && own_level.is_some_and(|own| current_level >= own) {
+        return Err(anyhow!(
+            "You can't change the level of a user at or above your own level"
+        ));
+    }
+    let admin_level = Int::from(ADMIN_LEVEL);
+    if current_level >= admin_level && level < admin_level {
+        let other_admins = power_levels
+            .users
+            .iter()
+            .filter(|(id, l)| *id != user_id && **l >= admin_level)
+            .count();
+        // Since room version 12, creators have an infinite level and are not in `users`.
+        let creators = room
+            .creators()
+            .unwrap_or_default()
+            .iter()
+            .filter(|creator| level_value(power_levels.for_user(creator)).is_none())
+            .count();
+        if other_admins + creators == 0 {
+            return Err(anyhow!("The last administrator of a room can't be demoted"));
+        }
+    }
+
+    room.update_power_levels(vec![(user_id, level)]).await?;
+    Ok(())
+}
+
+pub async fn set_power_level_threshold(
+    room_id: &RoomId,
+    threshold: PowerLevelThreshold,
+    level: Option<i64>,
+) -> anyhow::Result<()> {
+    let room = get_room(room_id)?;
+    let mut power_levels = room.power_levels().await?;
+    let own_user_id = room.own_user_id();
+    ensure_can_edit(&power_levels, own_user_id)?;
+
+    let level = level.map(Int::try_from).transpose()?;
+    let own_level = level_value(power_levels.for_user(own_user_id));
+    if let Some(level) = level {
+        ensure_can_grant(own_level, level)?;
+    }
+
+    let field = match threshold {
+        PowerLevelThreshold::Event { event_type } => {
+            let event_type = TimelineEventType::from(event_type.as_str());
+            if let Some(current) = power_levels.events.get(&event_type) {
+                ensure_can_grant(own_level, *current)?;
+            }
+            match level {
+                Some(level) => power_levels.events.insert(event_type, level),
+                None => power_levels.events.remove(&event_type),
+            };
+            None
+        }
+        PowerLevelThreshold::Ban => Some(&mut power_levels.ban),
+        PowerLevelThreshold::Kick => Some(&mut power_levels.kick),
+        PowerLevelThreshold::Invite => Some(&mut power_levels.invite),
+        PowerLevelThreshold::Redact => Some(&mut power_levels.redact),
+        PowerLevelThreshold::EventsDefault => Some(&mut power_levels.events_default),
+        PowerLevelThreshold::StateDefault => Some(&mut power_levels.state_default),
+        PowerLevelThreshold::UsersDefault => Some(&mut power_levels.users_default),
+        PowerLevelThreshold::NotifyRoom => Some(&mut power_levels.notifications.room),
+    };
+    if let Some(field) = field {
+        let level = level.ok_or(anyhow!("This threshold can't be unset"))?;
+        // A threshold above our own level couldn't be changed back.
+        ensure_can_grant(own_level, *field)?;
+        *field = level;
+    }
+
+    room.send_state_event(RoomPowerLevelsEventContent::try_from(power_levels)?)
+        .await?;
+    Ok(())
+}
+
+/// Role presets shared by all rooms, persisted in the app data directory.
+pub struct RolePresets {
+    dir: PathBuf,
+    custom: Mutex<Vec<RolePreset>>,
+}
+
+impl RolePresets {
+    pub fn load(app_data_dir: PathBuf) -> Self {
+        let custom = match std::fs::read_to_string(app_data_dir.join(PRESETS_FILE_NAME)) {
+            Ok(raw) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+                error!("Couldn't deserialize role presets. {e}");
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self {
+            dir: app_data_dir,
+            custom: Mutex::new(custom),
+        }
+    }
+
+    fn persist(&self, custom: &[RolePreset]) -> anyhow::Result<()> {
+        write_atomically(
+            &self.dir.join(PRESETS_FILE_NAME),
+            serde_json::to_string(custom)?,
+        )
+    }
+
+    /// Built-in presets first, then the custom ones, by decreasing level.
+    pub fn get(&self) -> Vec<RolePreset> {
+        with_built_in(&self.custom.lock().expect("Failed to lock mutex"))
+    }
+
+    /// Create or update a custom preset.
+    pub fn save(&self, name: String, level: i64) -> anyhow::Result<()> {
+        Int::try_from(level)?;
+        let mut custom = self.custom.lock().expect("Failed to lock mutex");
+        let name = validate_preset_name(&with_built_in(&custom), &name)?;
+        let mut updated = custom.clone();
+        match updated.iter_mut().find(|p| p.name == name) {
+            Some(preset) => preset.level = level,
+            None => updated.push(RolePreset {
+                name,
+                level,
+                built_in: false,
+            }),
+        }
+        self.persist(&updated)?;
+        *custom = updated;
+        Ok(())
+    }
+
+    pub fn delete(&self, name: &str) -> anyhow::Result<()> {
+        let mut custom = self.custom.lock().expect("Failed to lock mutex");
+        let mut updated = custom.clone();
+        updated.retain(|p| p.name != name);
+        self.persist(&updated)?;
+        *custom = updated;
+        Ok(())
+    }
+}
+
+fn with_built_in(custom: &[RolePreset]) -> Vec<RolePreset> {
+    let mut presets = vec![
+        RolePreset {
+            name: "admin".to_owned(),
+            level: ADMIN_LEVEL.into(),
+            built_in: true,
+        },
+        RolePreset {
+            name: "moderator".to_owned(),
+            level: MODERATOR_LEVEL.into(),
+            built_in: true,
+        },
+    ];
+    let mut custom = custom.to_vec();
+    custom.sort_by_key(|p| std::cmp::Reverse(p.level));
+    presets.extend(custom);
+    presets
+}
+
+/// A preset name is trimmed, and must not be empty nor be another preset's name
+/// with a different case. An exact match is the preset being updated.
+fn validate_preset_name(presets: &[RolePreset], name: &str) -> anyhow::Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(anyhow!("A role preset must have a name"));
+    }
+    let Some(existing) = presets.iter().find(|p| p.name.eq_ignore_ascii_case(name)) else {
+        return Ok(name.to_owned());
+    };
+    if existing.built_in {
+        return Err(anyhow!("Built-in role presets can't be changed"));
+    }
+    if existing.name != name {
+        return Err(anyhow!(
+            "A role preset named \"{}\" already exists",
+            existing.name
+        ));
+    }
+    Ok(name.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn presets() -> Vec<RolePreset> {
+        vec![
+            RolePreset {
+                name: "admin".to_owned(),
+                level: ADMIN_LEVEL.into(),
+                built_in: true,
+            },
+            RolePreset {
+                name: "Helper".to_owned(),
+                level: 10,
+                built_in: false,
+            },
+        ]
+    }
+
+    #[test]
+    fn trims_new_preset_names() {
+        assert_eq!(
+            validate_preset_name(&presets(), "  Guide ").unwrap(),
+            "Guide"
+        );
+        assert_eq!(
+            validate_preset_name(&presets(), "Helper").unwrap(),
+            "Helper"
+        );
+    }
+
+    #[test]
+    fn rejects_empty_names() {
+        assert!(validate_preset_name(&presets(), "").is_err());
+        assert!(validate_preset_name(&presets(), "   ").is_err());
+    }
+
+    #[test]
+    fn rejects_duplicate_and_built_in_names() {
+        assert!(validate_preset_name(&presets(), "helper").is_err());
+        assert!(validate_preset_name(&presets(), "Admin").is_err());
+        assert!(validate_preset_name(&presets(), "admin").is_err());
+    }
+}
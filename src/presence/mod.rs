@@ -0,0 +1,368 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use matrix_sdk::Client;
+use matrix_sdk::ruma::api::client::presence::{get_presence, set_presence};
+use matrix_sdk::ruma::events::presence::PresenceEvent;
+use matrix_sdk::ruma::presence::PresenceState;
+use matrix_sdk::ruma::{OwnedUserId, UserId};
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_svelte::ManagerExt;
+use tracing::{error, warn};
+
+use crate::direct_messages::get_dm_partners;
+use crate::state_updaters::{LOGIN_STATE_STORE_ID, ROOM_STORE_ID, ROOMS_COLLECTION_STORE_ID};
+use crate::utils::{get_client, wait_for_client};
+
+const IDLE_CHECK_TICK: Duration = Duration::from_secs(10);
+const DM_PARTNERS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+const PRESENCE_POLL_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Presence is fetched one user at a time, so we don't fetch it for every member of big rooms.
+const MAX_FETCHED_MEMBERS: usize = 50;
+
+/// The presence of another user, as pushed into the stores.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserPresence {
+    pub presence: PresenceState,
+    /// When the user was last active, in milliseconds since the Unix epoch.
+    pub last_active_ts: Option<u64>,
+    pub currently_active: bool,
+    pub status_msg: Option<String>,
+}
+
+impl UserPresence {
+    fn new(
+        presence: PresenceState,
+        last_active_ago: Option<Duration>,
+        currently_active: Option<bool>,
+        status_msg: Option<String>,
+    ) -> Self {
+        let last_active_ts = last_active_ago.and_then(|ago| {
+            SystemTime::now()
+                .checked_sub(ago)?
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|ts| ts.as_millis() as u64)
+        });
+        Self {
+            presence,
+            last_active_ts,
+            currently_active: currently_active.unwrap_or(false),
+            status_msg: status_msg.filter(|s| !s.is_empty()),
+        }
+    }
+}
+
+struct OwnPresence {
+    /// The presence chosen by the user.
+    chosen: PresenceState,
+    status_msg: Option<String>,
+    /// Whether we switched to `unavailable` because the user is inactive.
+    is_idle: bool,
+}
+
+/// Our own presence and the presence of the users displayed in the frontend.
+pub struct PresenceTracker {
+    own: Mutex<OwnPresence>,
+    last_activity: Mutex<Instant>,
+    idle_timeout: Mutex<Duration>,
+    /// The joined members of the currently opened room.
+    room_members: Mutex<Vec<OwnedUserId>>,
+    dm_partners: Mutex<Vec<OwnedUserId>>,
+    users: Mutex<HashMap<OwnedUserId, UserPresence>>,
+}
+
+impl Default for PresenceTracker {
+    fn default() -> Self {
+        Self {
+            own: Mutex::new(OwnPresence {
+                chosen: PresenceState::Online,
+                status_msg: None,
+                is_idle: false,
+            }),
+            last_activity: Mutex::new(Instant::now()),
+            idle_timeout: Mutex::new(DEFAULT_IDLE_TIMEOUT),
+            room_members: Mutex::new(Vec::new()),
+            dm_partners: Mutex::new(Vec::new()),
+            users: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl PresenceTracker {
+    /// Keep the joined members of a serialized `RoomScreen`.
+    pub fn update_from_room_screen(&self, room_screen: &Value) {
+        let Some(members) = room_screen.get("members").and_then(Value::as_object) else {
+            return;
+        };
+        *self.room_members.lock().expect("Failed to lock mutex") = members
+            .iter()
+            .filter(|(_, m)| m.get("membership").and_then(Value::as_str) == Some("join"))
+            .filter_map(|(user_id, _)| UserId::parse(user_id).ok())
+            .take(MAX_FETCHED_MEMBERS)
+            .collect();
+    }
+
+    pub fn set_idle_timeout(&self, timeout: Duration) -> anyhow::Result<()> {
+        if timeout.is_zero() {
+            return Err(anyhow::anyhow!("The idle timeout can't be zero"));
+        }
+        *self.idle_timeout.lock().expect("Failed to lock mutex") = timeout;
+        Ok(())
+    }
+
+    fn is_displayed(&self, user_id: &UserId) -> bool {
+        self.room_members
+            .lock()
+            .expect("Failed to lock mutex")
+            .iter()
+            .chain(
+                self.dm_partners
+                    .lock()
+                    .expect("Failed to lock mutex")
+                    .iter(),
+            )
+            .any(|id| id == user_id)
+    }
+
+    fn presences_of<'a>(
+        &self,
+        user_ids: impl IntoIterator<Item = &'a OwnedUserId>,
+    ) -> HashMap<OwnedUserId, UserPresence> {
+        let users = self.users.lock().expect("Failed to lock mutex");
+        user_ids
+            .into_iter()
+            .filter_map(|id| Some((id.clone(), users.get(id)?.clone())))
+            .collect()
+    }
+
+    /// Push the presence of the current room members and of the DM partners into the stores.
+    fn mirror<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        let room_members = self
+            .room_members
+            .lock()
+            .expect("Failed to lock mutex")
+            .clone();
+        let dm_partners = self
+            .dm_partners
+            .lock()
+            .expect("Failed to lock mutex")
+            .clone();
+        let member_presence = serde_json::to_value(self.presences_of(&room_members))
+            .expect("Couldn't serialize presence");
+        if let Err(e) = app_handle
+            .svelte()
+            .set(ROOM_STORE_ID, "memberPresence", member_presence)
+        {
+            error!("Couldn't push presence to the room store. {e}");
+        }
+        let dm_presence = serde_json::to_value(self.presences_of(&dm_partners))
+            .expect("Couldn't serialize presence");
+        if let Err(e) =
+            app_handle
+                .svelte()
+                .set(ROOMS_COLLECTION_STORE_ID, "dmPresence", dm_presence)
+        {
+            error!("Couldn't push presence to the rooms collection store. {e}");
+        }
+    }
+}
+
+async fn send_own_presence<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    presence: PresenceState,
+    status_msg: Option<String>,
+) -> anyhow::Result<()> {
+    let client = get_client()?;
+    let user_id = client
+        .user_id()
+        .ok_or(anyhow::anyhow!("The client isn't logged in"))?;
+    let mut request = set_presence::v3::Request::new(user_id.to_owned(), presence.clone());
+    request.status_msg = status_msg.clone();
+    client.send(request).await?;
+
+    let store = app_handle.svelte();
+    store.set(LOGIN_STATE_STORE_ID, "presence", presence.as_str())?;
+    store.set(LOGIN_STATE_STORE_ID, "statusMessage", status_msg)?;
+    Ok(())
+}
+
+/// Set our own presence and status message.
+/// The idle detector only switches to `unavailable` when the chosen presence is `online`.
+pub async fn set_own_presence<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    presence: PresenceState,
+    status_msg: Option<String>,
+) -> anyhow::Result<()> {
+    let status_msg = status_msg.filter(|s| !s.is_empty());
+    send_own_presence(app_handle, presence.clone(), status_msg.clone()).await?;
+    *app_handle
+        .state::<PresenceTracker>()
+        .own
+        .lock()
+        .expect("Failed to lock mutex") = OwnPresence {
+        chosen: presence,
+        status_msg,
+        is_idle: false,
+    };
+    Ok(())
+}
+
+/// Called by the frontend on user interactions. Switches back to `online` if we were idle.
+pub async fn report_user_activity<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<()> {
+    let tracker = app_handle.state::<PresenceTracker>();
+    *tracker.last_activity.lock().expect("Failed to lock mutex") = Instant::now();
+    let status_msg = {
+        let mut own = tracker.own.lock().expect("Failed to lock mutex");
+        if !own.is_idle {
+            return Ok(());
+        }
+        own.is_idle = false;
+        own.status_msg.clone()
+    };
+    send_own_presence(app_handle, PresenceState::Online, status_msg).await
+}
+
+async fn check_idle<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<()> {
+    let tracker = app_handle.state::<PresenceTracker>();
+    let inactive_for = tracker
+        .last_activity
+        .lock()
+        .expect("Failed to lock mutex")
+        .elapsed();
+    if inactive_for < *tracker.idle_timeout.lock().expect("Failed to lock mutex") {
+        return Ok(());
+    }
+    let status_msg = {
+        let mut own = tracker.own.lock().expect("Failed to lock mutex");
+        if own.is_idle || own.chosen != PresenceState::Online {
+            return Ok(());
+        }
+        own.is_idle = true;
+        own.status_msg.clone()
+    };
+    send_own_presence(app_handle, PresenceState::Unavailable, status_msg).await
+}
+
+async fn fetch_presence(client: &Client, user_id: &UserId) -> anyhow::Result<UserPresence> {
+    let response = client
+        .send(get_presence::v3::Request::new(user_id.to_owned()))
+        .await?;
+    Ok(UserPresence::new(
+        response.presence,
+        response.last_active_ago,
+        response.currently_active,
+        response.status_msg,
+    ))
+}
+
+async fn refresh_dm_partners<R: Runtime>(app_handle: &AppHandle<R>, client: &Client) {
+    match get_dm_partners(client).await {
+        Ok(dm_partners) => {
+            let tracker = app_handle.state::<PresenceTracker>();
+            *tracker.dm_partners.lock().expect("Failed to lock mutex") = dm_partners;
+            tracker.mirror(app_handle);
+        }
+        Err(e) => warn!("Couldn't get the DM partners. {e}"),
+    }
+}
+
+/// Fetch the presence of the displayed users that wasn't fetched recently. The sliding sync
+/// doesn't deliver presence events, so it must be polled to stay up to date.
+async fn poll_presence<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    client: &Client,
+    fetched_at: &mut HashMap<OwnedUserId, Instant>,
+) {
+    let tracker = app_handle.state::<PresenceTracker>();
+    let due: HashSet<OwnedUserId> = {
+        let room_members = tracker.room_members.lock().expect("Failed to lock mutex");
+        let dm_partners = tracker.dm_partners.lock().expect("Failed to lock mutex");
+        room_members
+            .iter()
+            .chain(dm_partners.iter())
+            .filter(|id| {
+                client.user_id() != Some(*id)
+                    && fetched_at
+                        .get(*id)
+                        .is_none_or(|t| t.elapsed() >= PRESENCE_POLL_INTERVAL)
+            })
+            .cloned()
+            .collect()
+    };
+    if due.is_empty() {
+        return;
+    }
+
+    let mut changed = false;
+    for user_id in due {
+        match fetch_presence(client, &user_id).await {
+            Ok(presence) => {
+                tracker
+                    .users
+                    .lock()
+                    .expect("Failed to lock mutex")
+                    .insert(user_id.clone(), presence);
+                fetched_at.insert(user_id, Instant::now());
+                changed = true;
+            }
+            // Presence may be disabled on the homeserver, there's no point in trying the others.
+            // The remaining users are tried again on the next tick.
+            Err(e) => {
+                warn!("Couldn't get the presence of {user_id}. {e}");
+                break;
+            }
+        }
+    }
+    if changed {
+        tracker.mirror(app_handle);
+    }
+}
+
+/// Track presence and detect inactivity. It is started once in the plugin setup.
+pub async fn run_presence_loop<R: Runtime>(app_handle: AppHandle<R>) {
+    let client = wait_for_client().await;
+
+    let handler_handle = app_handle.clone();
+    client.add_event_handler(move |event: PresenceEvent| {
+        let app_handle = handler_handle.clone();
+        async move {
+            let tracker = app_handle.state::<PresenceTracker>();
+            let content = event.content;
+            let is_displayed = tracker.is_displayed(&event.sender);
+            tracker.users.lock().expect("Failed to lock mutex").insert(
+                event.sender,
+                UserPresence::new(
+                    content.presence,
+                    content
+                        .last_active_ago
+                        .map(|ago| Duration::from_millis(ago.into())),
+                    content.currently_active,
+                    content.status_msg,
+                ),
+            );
+            if is_displayed {
+                tracker.mirror(&app_handle);
+            }
+        }
+    });
+
+    let mut last_dm_partners_refresh: Option<Instant> = None;
+    let mut fetched_at = HashMap::new();
+    loop {
+        if let Err(e) = check_idle(&app_handle).await {
+            warn!("Couldn't update presence after inactivity. {e}");
+        }
+        if last_dm_partners_refresh.is_none_or(|t| t.elapsed() >= DM_PARTNERS_REFRESH_INTERVAL) {
+            refresh_dm_partners(&app_handle, &client).await;
+            last_dm_partners_refresh = Some(Instant::now());
+        }
+        poll_presence(&app_handle, &client, &mut fetched_at).await;
+        tokio::time::sleep(IDLE_CHECK_TICK).await;
+    }
+}
@@ -0,0 +1,150 @@
+use std::future::IntoFuture;
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use futures::StreamExt;
+use futures::channel::oneshot;
+use futures::future::{self, Either};
+use matrix_sdk::authentication::oauth::qrcode::{
+    CheckCodeSender, GeneratedQrProgress, GrantLoginProgress,
+};
+use serde::Serialize;
+use tauri::ipc::Channel;
+use tauri::{AppHandle, Listener, Manager, Runtime};
+use tracing::warn;
+
+use crate::utils::get_client;
+
+/// Emitted by the frontend to cancel the login being granted.
+const CANCEL_QR_LOGIN_EVENT: &str = "matrix-svelte://cancel-qr-login";
+
+/// The progress of a login granted to a new device, sent through a channel.
+#[derive(Debug, Clone, Serialize)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "event",
+    content = "data"
+)]
+pub enum QrLoginEvent {
+    /// The QR code to show to the new device, as base64. It can also be pasted as is.
+    QrReady {
+        qr_code: String,
+    },
+    /// The new device scanned the QR code, the code it displays must be submitted
+    /// with `submit_qr_login_check_code`.
+    CheckCodeRequested,
+    /// The new device must be approved on this page of the homeserver.
+    WaitingForApproval {
+        verification_uri: String,
+    },
+    /// The cross-signing and backup secrets are being sent to the new device.
+    SyncingSecrets,
+    Done,
+    Cancelled {
+        reason: String,
+    },
+}
+
+/// The login being granted, waiting for the check code displayed by the new device.
+#[derive(Default)]
+pub struct QrLoginState {
+    check_code_sender: Mutex<Option<CheckCodeSender>>,
+}
+
+impl QrLoginState {
+    fn set_check_code_sender(&self, sender: Option<CheckCodeSender>) {
+        *self.check_code_sender.lock().expect("Failed to lock mutex") = sender;
+    }
+}
+
+fn map_progress<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    progress: GrantLoginProgress<GeneratedQrProgress>,
+) -> Option<QrLoginEvent> {
+    match progress {
+        GrantLoginProgress::EstablishingSecureChannel(GeneratedQrProgress::QrReady(qr)) => {
+            Some(QrLoginEvent::QrReady {
+                qr_code: BASE64.encode(qr.to_bytes()),
+            })
+        }
+        GrantLoginProgress::EstablishingSecureChannel(GeneratedQrProgress::QrScanned(sender)) => {
+            app_handle
+                .state::<QrLoginState>()
+                .set_check_code_sender(Some(sender));
+            Some(QrLoginEvent::CheckCodeRequested)
+        }
+        GrantLoginProgress::WaitingForAuth { verification_uri } => {
+            Some(QrLoginEvent::WaitingForApproval {
+                verification_uri: verification_uri.to_string(),
+            })
+        }
+        GrantLoginProgress::SyncingSecrets => Some(QrLoginEvent::SyncingSecrets),
+        // The end of the login is reported once the whole operation returns.
+        GrantLoginProgress::Starting | GrantLoginProgress::Done => None,
+    }
+}
+
+/// Let a new device log in by scanning a QR code generated by this one (MSC4108).
+/// The login can be cancelled by emitting `matrix-svelte://cancel-qr-login`.
+pub async fn grant_qr_login<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    on_event: Channel<QrLoginEvent>,
+) -> anyhow::Result<()> {
+    let grant = get_client()?.oauth().grant_login_with_qr_code().generate();
+    let mut progress = grant.subscribe_to_progress();
+
+    let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
+    let cancel_tx = Mutex::new(Some(cancel_tx));
+    let listener_id = app_handle.listen(CANCEL_QR_LOGIN_EVENT, move |_| {
+        if let Some(tx) = cancel_tx.lock().expect("Failed to lock mutex").take() {
+            let _ = tx.send(());
+        }
+    });
+
+    let progress_handle = app_handle.clone();
+    let progress_channel = on_event.clone();
+    let forwarder = tauri::async_runtime::spawn(async move {
+        while let Some(state) = progress.next().await {
+            if let Some(event) = map_progress(&progress_handle, state)
+                && let Err(e) = progress_channel.send(event)
+            {
+                warn!("Couldn't send the QR login progress. {e}");
+            }
+        }
+    });
+
+    let result = match future::select(Box::pin(grant.into_future()), cancel_rx).await {
+        Either::Left((result, _)) => result.map_err(anyhow::Error::from),
+        Either::Right(_) => Err(anyhow!("The login was cancelled")),
+    };
+    app_handle.unlisten(listener_id);
+    forwarder.abort();
+    app_handle
+        .state::<QrLoginState>()
+        .set_check_code_sender(None);
+
+    let event = match &result {
+        Ok(()) => QrLoginEvent::Done,
+        Err(e) => QrLoginEvent::Cancelled {
+            reason: e.to_string(),
+        },
+    };
+    on_event.send(event)?;
+    result
+}
+
+/// Submit the two-digit code displayed by the new device once it scanned the QR code.
+pub async fn submit_check_code(state: &QrLoginState, code: u8) -> anyhow::Result<()> {
+    let sender = state
+        .check_code_sender
+        .lock()
+        .expect("Failed to lock mutex")
+        .take()
+        .ok_or(anyhow!("No login is waiting for a check code"))?;
+    sender
+        .send(code)
+        .await
+        .map_err(|_| anyhow!("The login isn't waiting for a check code anymore"))
+}
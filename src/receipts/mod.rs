@@ -0,0 +1,129 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::anyhow;
+use matrix_sdk::Room;
+use matrix_sdk::room::{IncludeRelations, RelationsOptions};
+use matrix_sdk::ruma::events::receipt::{ReceiptThread, ReceiptType};
+use matrix_sdk::ruma::events::relation::RelationType;
+use matrix_sdk::ruma::events::{AnySyncMessageLikeEvent, AnySyncTimelineEvent};
+use matrix_sdk::ruma::{EventId, MilliSecondsSinceUnixEpoch, OwnedMxcUri, OwnedUserId, RoomId};
+use serde::Serialize;
+
+use crate::utils::get_client;
+
+/// A room member resolved from the room member list, along with
+/// the time of its receipt or reaction.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedMember {
+    pub user_id: OwnedUserId,
+    pub display_name: Option<String>,
+    pub avatar: Option<OwnedMxcUri>,
+    pub timestamp: Option<MilliSecondsSinceUnixEpoch>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventReceiptsAndReactions {
+    /// Members whose read receipt points to this event, latest first.
+    pub readers: Vec<ResolvedMember>,
+    /// Senders of each reaction, grouped by key.
+    pub reactions: BTreeMap<String, Vec<ResolvedMember>>,
+}
+
+pub async fn get_event_receipts_and_reactions(
+    room_id: &RoomId,
+    event_id: &EventId,
+) -> anyhow::Result<EventReceiptsAndReactions> {
+    let room = get_client()?
+        .get_room(room_id)
+        .ok_or(anyhow!("Room {room_id} not found"))?;
+
+    // Receipts may be sent either unthreaded or for the main thread, we keep the latest one.
+    let mut receipts: HashMap<OwnedUserId, Option<MilliSecondsSinceUnixEpoch>> = HashMap::new();
+    for thread in [ReceiptThread::Unthreaded, ReceiptThread::Main] {
+        for (user_id, receipt) in room
+            .load_event_receipts(ReceiptType::Read, thread, event_id)
+            .await?
+        {
+            let ts = receipts.entry(user_id).or_default();
+            if receipt.ts > *ts {
+                *ts = receipt.ts;
+            }
+        }
+    }
+    let mut readers = Vec::with_capacity(receipts.len());
+    for (user_id, timestamp) in receipts {
+        readers.push(resolve_member(&room, user_id, timestamp).await);
+    }
+    readers.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+    let mut reactions: BTreeMap<String, Vec<ResolvedMember>> = BTreeMap::new();
+    let mut from = None;
+    loop {
+        let relations = room
+            .relations(
+                event_id.to_owned(),
+                RelationsOptions {
+                    from,
+                    include_relations: IncludeRelations::RelationsOfType(RelationType::Annotation),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        for event in relations.chunk {
+            let Ok(AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::Reaction(reaction))) =
+                event.raw().deserialize()
+            else {
+                continue;
+            };
+            let Some(reaction) = reaction.as_original() else {
+                continue;
+            };
+            let senders = reactions
+                .entry(reaction.content.relates_to.key.clone())
+                .or_default();
+            // A sender can only react once with a given key.
+            if senders.iter().any(|m| m.user_id == reaction.sender) {
+                continue;
+            }
+            senders.push(
+                resolve_member(
+                    &room,
+                    reaction.sender.clone(),
+                    Some(reaction.origin_server_ts),
+                )
+                .await,
+            );
+        }
+
+        match relations.next_batch_token {
+            Some(token) => from = Some(token),
+            None => break,
+        }
+    }
+    for senders in reactions.values_mut() {
+        senders.sort_by_key(|m| m.timestamp);
+    }
+
+    Ok(EventReceiptsAndReactions { readers, reactions })
+}
+
+async fn resolve_member(
+    room: &Room,
+    user_id: OwnedUserId,
+    timestamp: Option<MilliSecondsSinceUnixEpoch>,
+) -> ResolvedMember {
+    let member = room.get_member_no_sync(&user_id).await.ok().flatten();
+    ResolvedMember {
+        display_name: member
+            .as_ref()
+            .and_then(|m| m.display_name().map(ToOwned::to_owned)),
+        avatar: member
+            .as_ref()
+            .and_then(|m| m.avatar_url().map(ToOwned::to_owned)),
+        user_id,
+        timestamp,
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use matrix_sdk::Client;
+use matrix_sdk::ruma::api::client::account::{
+    check_registration_token_validity, get_username_availability, register,
+    request_registration_token_via_email,
+};
+use matrix_sdk::ruma::api::client::error::ErrorKind;
+use matrix_sdk::ruma::{ClientSecret, OwnedUserId, UInt};
+use matrix_ui_serializable::models::events::MatrixLoginPayload;
+use serde::Serialize;
+use serde_json::Value;
+use url::Url;
+
+use crate::LOGIN_SENDER;
+use crate::account::ThirdPartyValidation;
+use crate::uia::{UiaOutcome, UiaSessions, flows_of, params_of};
+
+/// The registration flows offered by a homeserver.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistrationFlows {
+    /// The homeserver URL, resolved from the server name if needed.
+    pub homeserver_url: String,
+    /// Each flow is a list of stage types, i.e. `m.login.registration_token`.
+    pub flows: Vec<Vec<String>>,
+    /// The parameters of each stage, by stage type, i.e. the policies of `m.login.terms`.
+    pub params: Value,
+}
+
+/// The unauthenticated client used to register, before the user is logged in.
+#[derive(Default)]
+pub struct RegistrationClient(Mutex<Option<Client>>);
+
+impl RegistrationClient {
+    fn get(&self) -> anyhow::Result<Client> {
+        self.0
+            .lock()
+            .expect("Failed to lock mutex")
+            .clone()
+            .ok_or(anyhow!("No registration in progress"))
+    }
+
+    pub fn homeserver(&self) -> anyhow::Result<Url> {
+        Ok(self.get()?.homeserver())
+    }
+}
+
+/// Discover the registration flows of a homeserver. It must be called before the other
+/// registration functions. Homeservers using OAuth handle registration in their own web page.
+pub async fn get_registration_flows(
+    state: &RegistrationClient,
+    homeserver: &str,
+) -> anyhow::Result<RegistrationFlows> {
+    let client = Client::builder()
+        .server_name_or_homeserver_url(homeserver)
+        .build()
+        .await?;
+    *state.0.lock().expect("Failed to lock mutex") = Some(client.clone());
+
+    // Without auth data, the homeserver answers with the flows instead of registering.
+    let error = match client.send(register::v3::Request::new()).await {
+        Ok(_) => return Err(anyhow!("Unexpected registration without username")),
+        Err(e) => e,
+    };
+    if let Some(info) = error.as_uiaa_response() {
+        return Ok(RegistrationFlows {
+            homeserver_url: client.homeserver().to_string(),
+            flows: flows_of(info),
+            params: params_of(info),
+        });
+    }
+    if error.client_api_error_kind() == Some(&ErrorKind::Forbidden) {
+        return Err(anyhow!("Registration is disabled on this homeserver"));
+    }
+    Err(error.into())
+}
+
+/// Whether a username can be registered. Invalid or reserved usernames are errors.
+pub async fn check_username_availability(
+    state: &RegistrationClient,
+    username: &str,
+) -> anyhow::Result<bool> {
+    let request = get_username_availability::v3::Request::new(username.to_owned());
+    match state.get()?.send(request).await {
+        Ok(response) => Ok(response.available),
+        Err(e) if e.client_api_error_kind() == Some(&ErrorKind::UserInUse) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Whether a token can be used for the `m.login.registration_token` stage.
+pub async fn check_registration_token(
+    state: &RegistrationClient,
+    token: &str,
+) -> anyhow::Result<bool> {
+    let request = check_registration_token_validity::v1::Request::new(token.to_owned());
+    Ok(state.get()?.send(request).await?.valid)
+}
+
+/// Ask the homeserver to send a validation email, for the `m.login.email.identity` stage.
+/// `send_attempt` must be incremented to send the email again.
+pub async fn request_registration_email_token(
+    state: &RegistrationClient,
+    email: String,
+    send_attempt: u64,
+) -> anyhow::Result<ThirdPartyValidation> {
+    let client_secret = ClientSecret::new();
+    let request = request_registration_token_via_email::v3::Request::new(
+        client_secret.clone(),
+        email,
+        UInt::try_from(send_attempt)?,
+    );
+    let response = state.get()?.send(request).await?;
+    Ok(ThirdPartyValidation {
+        sid: response.sid,
+        client_secret,
+        submit_url: response.submit_url,
+    })
+}
+
+/// Register an account, then log in with it. The stages of the flows continue with
+/// `continue_uia`. Once registered, the login goes through the same path as existing accounts
+/// so the session is persisted the same way.
+pub async fn register(
+    state: &RegistrationClient,
+    sessions: &UiaSessions,
+    username: String,
+    password: String,
+    client_name: String,
+) -> anyhow::Result<UiaOutcome> {
+    let client = state.get()?;
+    sessions
+        .start(move |auth| {
+            let client = client.clone();
+            let password = password.clone();
+            let client_name = client_name.clone();
+            let mut request = register::v3::Request::new();
+            request.username = Some(username.clone());
+            request.password = Some(password.clone());
+            request.inhibit_login = true;
+            request.auth = auth;
+            async move {
+                let user_id = client.send(request).await?.user_id;
+                log_in(&client, &user_id, password, client_name).await?;
+                Ok(user_id)
+            }
+        })
+        .await
+}
+
+async fn log_in(
+    client: &Client,
+    user_id: &OwnedUserId,
+    password: String,
+    client_name: String,
+) -> anyhow::Result<()> {
+    let sender = LOGIN_SENDER
+        .get()
+        .ok_or(anyhow!("The login receiver isn't ready yet"))?;
+    sender
+        .send(MatrixLoginPayload {
+            username: user_id.to_string(),
+            password,
+            homeserver_url: client.homeserver().to_string(),
+            client_name,
+        })
+        .await
+        .map_err(|_| anyhow!("Couldn't send the login request of the registered account"))
+}
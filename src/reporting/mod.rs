@@ -0,0 +1,185 @@
+use matrix_sdk::ruma::api::client::error::ErrorKind;
+use matrix_sdk::ruma::api::client::room::{report_content, report_room};
+use matrix_sdk::ruma::api::client::user::report_user;
+use matrix_sdk::ruma::{EventId, Int, OwnedUserId, RoomId, UserId};
+use matrix_sdk::{HttpError, Room};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Runtime};
+use tracing::warn;
+
+use crate::ignored_users::ignore_user;
+use crate::utils::{get_client, get_room};
+use crate::{Endpoint, Error, Result};
+
+/// What to do along with the report.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportActions {
+    /// Redact the reported event, if we are allowed to.
+    #[serde(default)]
+    pub redact: bool,
+    /// Ignore the sender of the reported event, or the reported user.
+    #[serde(default)]
+    pub ignore: bool,
+}
+
+/// What happened to an action requested along with a report.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind", content = "error")]
+pub enum ReportActionOutcome {
+    NotRequested,
+    Done,
+    /// We aren't allowed to redact this event in the room.
+    NotAllowed,
+    /// The report has been sent, but the action failed.
+    Failed(String),
+}
+
+/// The report itself succeeded, the actions may have not.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportResult {
+    pub redaction: ReportActionOutcome,
+    pub ignore: ReportActionOutcome,
+}
+
+impl ReportActionOutcome {
+    fn failed(error: anyhow::Error, action: &str) -> Self {
+        warn!("Couldn't {action} after the report. {error}");
+        Self::Failed(error.to_string())
+    }
+
+    fn from_result(result: anyhow::Result<()>, action: &str) -> Self {
+        result.map_or_else(|e| Self::failed(e, action), |()| Self::Done)
+    }
+}
+
+/// Homeservers that don't implement an endpoint answer with `M_UNRECOGNIZED`
+/// or a `405 Method Not Allowed`.
+fn map_report_error(error: HttpError, endpoint: Endpoint) -> Error {
+    let is_unsupported = error.client_api_error_kind() == Some(&ErrorKind::Unrecognized)
+        || error
+            .as_client_api_error()
+            .is_some_and(|e| e.status_code.as_u16() == 405);
+    if is_unsupported {
+        Error::UnsupportedEndpoint(endpoint)
+    } else {
+        anyhow::Error::from(error).into()
+    }
+}
+
+/// Report an event to the homeserver admins.
+/// The score ranges from -100 (most offensive) to 0 (inoffensive).
+pub async fn report_event<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    room_id: &RoomId,
+    event_id: &EventId,
+    reason: Option<String>,
+    score: Option<i64>,
+    actions: ReportActions,
+) -> Result<ReportResult> {
+    let room = get_room(room_id)?;
+    let score = score
+        .map(|s| Int::try_from(s.clamp(-100, 0)))
+        .transpose()
+        .map_err(anyhow::Error::from)?;
+
+    // The event may not be available anymore once redacted.
+    let sender = if actions.ignore || actions.redact {
+        let event = room
+            .event(event_id, None)
+            .await
+            .map_err(anyhow::Error::from)?;
+        Some(
+            event
+                .raw()
+                .get_field::<OwnedUserId>("sender")
+                .map_err(anyhow::Error::from)?
+                .ok_or(anyhow::anyhow!("The reported event has no sender"))?,
+        )
+    } else {
+        None
+    };
+
+    get_client()?
+        .send(report_content::v3::Request::new(
+            room_id.to_owned(),
+            event_id.to_owned(),
+            score,
+            reason.clone(),
+        ))
+        .await
+        .map_err(|e| map_report_error(e, Endpoint::EventReports))?;
+
+    let redaction = match &sender {
+        Some(sender) if actions.redact => {
+            match redact_if_allowed(&room, event_id, sender, reason.as_deref()).await {
+                Ok(true) => ReportActionOutcome::Done,
+                Ok(false) => ReportActionOutcome::NotAllowed,
+                Err(e) => ReportActionOutcome::failed(e, "redact the reported event"),
+            }
+        }
+        _ => ReportActionOutcome::NotRequested,
+    };
+    let ignore = match sender {
+        Some(sender) if actions.ignore => ReportActionOutcome::from_result(
+            ignore_user(app_handle, &sender).await,
+            "ignore the reported user",
+        ),
+        _ => ReportActionOutcome::NotRequested,
+    };
+    Ok(ReportResult { redaction, ignore })
+}
+
+/// Returns `false` if our power level doesn't allow to redact this event.
+async fn redact_if_allowed(
+    room: &Room,
+    event_id: &EventId,
+    sender: &UserId,
+    reason: Option<&str>,
+) -> anyhow::Result<bool> {
+    let own_user_id = room.own_user_id();
+    let allowed = if sender == own_user_id {
+        room.can_user_redact_own(own_user_id).await?
+    } else {
+        room.can_user_redact_other(own_user_id).await?
+    };
+    if allowed {
+        room.redact(event_id, reason, None).await?;
+    }
+    Ok(allowed)
+}
+
+/// Report a room to the homeserver admins.
+pub async fn report_room(room_id: &RoomId, reason: String) -> Result<()> {
+    get_client()?
+        .send(report_room::v3::Request::new(room_id.to_owned(), reason))
+        .await
+        .map_err(|e| map_report_error(e, Endpoint::RoomReports))?;
+    Ok(())
+}
+
+/// Report a user to the homeserver admins, and optionally ignore them.
+pub async fn report_user<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    user_id: &UserId,
+    reason: String,
+    actions: ReportActions,
+) -> Result<ReportResult> {
+    get_client()?
+        .send(report_user::v3::Request::new(user_id.to_owned(), reason))
+        .await
+        .map_err(|e| map_report_error(e, Endpoint::UserReports))?;
+    let ignore = if actions.ignore {
+        ReportActionOutcome::from_result(
+            ignore_user(app_handle, user_id).await,
+            "ignore the reported user",
+        )
+    } else {
+        ReportActionOutcome::NotRequested
+    };
+    Ok(ReportResult {
+        redaction: ReportActionOutcome::NotRequested,
+        ignore,
+    })
+}
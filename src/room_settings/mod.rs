@@ -0,0 +1,312 @@
+use anyhow::anyhow;
+use matrix_sdk::ruma::events::StateEventType;
+use matrix_sdk::ruma::events::room::guest_access::{GuestAccess, RoomGuestAccessEventContent};
+use matrix_sdk::ruma::events::room::history_visibility::{
+    HistoryVisibility, RoomHistoryVisibilityEventContent,
+};
+use matrix_sdk::ruma::events::room::join_rules::{
+    AllowRule, JoinRule, Restricted, RoomJoinRulesEventContent,
+};
+use matrix_sdk::ruma::events::room::server_acl::RoomServerAclEventContent;
+use matrix_sdk::ruma::{OwnedRoomId, RoomId, ServerName};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{get_room, get_state_event};
+
+/// Who can join the room.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "kind"
+)]
+pub enum JoinRuleSetting {
+    Public,
+    Invite,
+    Knock,
+    /// Members of the given spaces can join without an invite.
+    Restricted {
+        space_ids: Vec<OwnedRoomId>,
+    },
+    /// Like `Restricted`, but others can knock.
+    KnockRestricted {
+        space_ids: Vec<OwnedRoomId>,
+    },
+}
+
+impl JoinRuleSetting {
+    fn from_join_rule(join_rule: &JoinRule) -> Self {
+        let space_ids = |restricted: &Restricted| {
+            restricted
+                .allow
+                .iter()
+                .filter_map(|rule| match rule {
+                    AllowRule::RoomMembership(membership) => Some(membership.room_id.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        match join_rule {
+            JoinRule::Public => Self::Public,
+            JoinRule::Knock => Self::Knock,
+            JoinRule::Restricted(restricted) => Self::Restricted {
+                space_ids: space_ids(restricted),
+            },
+            JoinRule::KnockRestricted(restricted) => Self::KnockRestricted {
+                space_ids: space_ids(restricted),
+            },
+            // `private` is reserved by the spec and behaves like `invite`.
+            _ => Self::Invite,
+        }
+    }
+
+    fn into_join_rule(self) -> JoinRule {
+        let restricted = |space_ids: Vec<OwnedRoomId>| {
+            Restricted::new(
+                space_ids
+                    .into_iter()
+                    .map(AllowRule::room_membership)
+                    .collect(),
+            )
+        };
+        match self {
+            Self::Public => JoinRule::Public,
+            Self::Invite => JoinRule::Invite,
+            Self::Knock => JoinRule::Knock,
+            Self::Restricted { space_ids } => JoinRule::Restricted(restricted(space_ids)),
+            Self::KnockRestricted { space_ids } => JoinRule::KnockRestricted(restricted(space_ids)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerAcl {
+    pub allow_ip_literals: bool,
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+/// Which settings the current user is allowed to change.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomSettingsPermissions {
+    pub join_rule: bool,
+    pub history_visibility: bool,
+    pub guest_access: bool,
+    pub encryption: bool,
+    pub server_acl: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomSettings {
+    pub join_rule: JoinRuleSetting,
+    pub history_visibility: HistoryVisibility,
+    pub guest_access: GuestAccess,
+    pub is_encrypted: bool,
+    /// `None` if the room doesn't have any server ACL.
+    pub server_acl: Option<ServerAcl>,
+    pub can_edit: RoomSettingsPermissions,
+}
+
+/// The settings to change, the ones left to `None` are kept as is.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomSettingsUpdate {
+    pub join_rule: Option<JoinRuleSetting>,
+    pub history_visibility: Option<HistoryVisibility>,
+    pub guest_access: Option<GuestAccess>,
+    pub server_acl: Option<ServerAcl>,
+}
+
+pub async fn get_room_settings(room_id: &RoomId) -> anyhow::Result<RoomSettings> {
+    let room = get_room(room_id)?;
+
+    let join_rule =
+        get_state_event::<RoomJoinRulesEventContent>(&room, StateEventType::RoomJoinRules)
+            .await?
+            .map_or(JoinRuleSetting::Invite, |e| {
+                JoinRuleSetting::from_join_rule(&e.content.join_rule)
+            });
+    let history_visibility = get_state_event::<RoomHistoryVisibilityEventContent>(
+        &room,
+        StateEventType::RoomHistoryVisibility,
+    )
+    .await?
+    .map_or(HistoryVisibility::Shared, |e| e.content.history_visibility);
+    let guest_access =
+        get_state_event::<RoomGuestAccessEventContent>(&room, StateEventType::RoomGuestAccess)
+            .await?
+            .map_or(GuestAccess::Forbidden, |e| e.content.guest_access);
+    let server_acl =
+        get_state_event::<RoomServerAclEventContent>(&room, StateEventType::RoomServerAcl)
+            .await?
+            .map(|e| ServerAcl {
+                allow_ip_literals: e.content.allow_ip_literals,
+                allow: e.content.allow,
+                deny: e.content.deny,
+            });
+
+    let power_levels = room.power_levels().await?;
+    let own_user_id = room.own_user_id();
+    let can = |event_type| power_levels.user_can_send_state(own_user_id, event_type);
+
+    Ok(RoomSettings {
+        join_rule,
+        history_visibility,
+        guest_access,
+        is_encrypted: room.latest_encryption_state().await?.is_encrypted(),
+        server_acl,
+        can_edit: RoomSettingsPermissions {
+            join_rule: can(StateEventType::RoomJoinRules),
+            history_visibility: can(StateEventType::RoomHistoryVisibility),
+            guest_access: can(StateEventType::RoomGuestAccess),
+            encryption: can(StateEventType::RoomEncryption),
+            server_acl: can(StateEventType::RoomServerAcl),
+        },
+    })
+}
+
+fn ensure_allowed(allowed: bool, setting: &str) -> anyhow::Result<()> {
+    if !allowed {
+        return Err(anyhow!(
+            "You are not allowed to change the {setting} of this room"
+        ));
+    }
+    Ok(())
+}
+
+/// Apply the given changes, then return the new settings. Every change is validated before
+/// any is sent, so a rejected change doesn't leave the room partially updated.
+pub async fn update_room_settings(
+    room_id: &RoomId,
+    update: RoomSettingsUpdate,
+) -> anyhow::Result<RoomSettings> {
+    let room = get_room(room_id)?;
+    let current = get_room_settings(room_id).await?;
+
+    let join_rule = update
+        .join_rule
+        .map(|join_rule| {
+            ensure_allowed(current.can_edit.join_rule, "join rule")?;
+            Ok::<_, anyhow::Error>(RoomJoinRulesEventContent::new(join_rule.into_join_rule()))
+        })
+        .transpose()?;
+    let history_visibility = update
+        .history_visibility
+        .map(|history_visibility| {
+            ensure_allowed(current.can_edit.history_visibility, "history visibility")?;
+            Ok::<_, anyhow::Error>(RoomHistoryVisibilityEventContent::new(history_visibility))
+        })
+        .transpose()?;
+    let guest_access = update
+        .guest_access
+        .map(|guest_access| {
+            ensure_allowed(current.can_edit.guest_access, "guest access")?;
+            Ok::<_, anyhow::Error>(RoomGuestAccessEventContent::new(guest_access))
+        })
+        .transpose()?;
+    let server_acl = update
+        .server_acl
+        .map(|acl| {
+            ensure_allowed(current.can_edit.server_acl, "server ACL")?;
+            server_acl_content(acl, room.own_user_id().server_name())
+        })
+        .transpose()?;
+
+    if let Some(content) = join_rule {
+        room.send_state_event(content).await?;
+    }
+    if let Some(content) = history_visibility {
+        room.send_state_event(content).await?;
+    }
+    if let Some(content) = guest_access {
+        room.send_state_event(content).await?;
+    }
+    if let Some(content) = server_acl {
+        room.send_state_event(content).await?;
+    }
+
+    get_room_settings(room_id).await
+}
+
+/// Build the ACL content, rejecting the ones that would make the room unusable for us,
+/// either by an explicit or wildcard deny, or by an allow list without our server.
+fn server_acl_content(
+    acl: ServerAcl,
+    own_server: &ServerName,
+) -> anyhow::Result<RoomServerAclEventContent> {
+    let content = RoomServerAclEventContent::new(acl.allow_ip_literals, acl.allow, acl.deny);
+    if !content.is_allowed(own_server) {
+        return Err(anyhow!("This ACL would ban your own homeserver"));
+    }
+    Ok(content)
+}
+
+/// Enable end-to-end encryption in a room. This can't be undone, so the caller
+/// must explicitly confirm it.
+pub async fn enable_room_encryption(room_id: &RoomId, confirm: bool) -> anyhow::Result<()> {
+    if !confirm {
+        return Err(anyhow!(
+            "Encryption can't be disabled once enabled, this must be confirmed"
+        ));
+    }
+    let room = get_room(room_id)?;
+    let settings = get_room_settings(room_id).await?;
+    if settings.is_encrypted {
+        return Ok(());
+    }
+    if !settings.can_edit.encryption {
+        return Err(anyhow!(
+            "You are not allowed to enable encryption in this room"
+        ));
+    }
+    room.enable_encryption().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn acl(allow: &[&str], deny: &[&str]) -> ServerAcl {
+        ServerAcl {
+            allow_ip_literals: false,
+            allow: allow.iter().map(|s| s.to_string()).collect(),
+            deny: deny.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn own_server() -> &'static ServerName {
+        <&ServerName>::try_from("matrix.example.org").unwrap()
+    }
+
+    #[test]
+    fn accepts_an_acl_allowing_our_server() {
+        assert!(server_acl_content(acl(&["*"], &["evil.org"]), own_server()).is_ok());
+        assert!(server_acl_content(acl(&["*.example.org"], &[]), own_server()).is_ok());
+    }
+
+    #[test]
+    fn rejects_denying_our_server() {
+        assert!(server_acl_content(acl(&["*"], &["matrix.example.org"]), own_server()).is_err());
+        assert!(server_acl_content(acl(&["*"], &["*.example.org"]), own_server()).is_err());
+        assert!(server_acl_content(acl(&["*"], &["matrix.example.???"]), own_server()).is_err());
+    }
+
+    #[test]
+    fn rejects_an_allow_list_without_our_server() {
+        assert!(server_acl_content(acl(&["friends.org"], &[]), own_server()).is_err());
+        assert!(server_acl_content(acl(&[], &[]), own_server()).is_err());
+    }
+
+    #[test]
+    fn rejects_ip_literals_when_our_server_is_one() {
+        let own_server = <&ServerName>::try_from("127.0.0.1:8448").unwrap();
+        assert!(server_acl_content(acl(&["*"], &[]), own_server).is_err());
+        let mut allowing_ips = acl(&["*"], &[]);
+        allowing_ips.allow_ip_literals = true;
+        assert!(server_acl_content(allowing_ips, own_server).is_ok());
+    }
+}
@@ -0,0 +1,209 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use matrix_sdk::deserialized_responses::SyncOrStrippedState;
+use matrix_sdk::ruma::api::client::discovery::get_capabilities::{self, v3::RoomVersionStability};
+use matrix_sdk::ruma::api::client::room::upgrade_room;
+use matrix_sdk::ruma::events::room::create::RoomCreateEventContent;
+use matrix_sdk::ruma::events::room::tombstone::RoomTombstoneEventContent;
+use matrix_sdk::ruma::events::{StateEventType, SyncStateEvent};
+use matrix_sdk::ruma::{
+    OwnedEventId, OwnedRoomId, OwnedServerName, OwnedUserId, RoomId, RoomOrAliasId, RoomVersionId,
+};
+use matrix_sdk::{Room, RoomMemberships, RoomState};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::utils::{get_client, get_room, get_state_event};
+
+/// How long to wait for the new room to come down the sync before giving up the invites.
+const NEW_ROOM_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomUpgradeResult {
+    pub new_room_id: OwnedRoomId,
+    /// Users with a pending invite in the old room that couldn't be invited in the new one.
+    pub failed_invites: Vec<OwnedUserId>,
+}
+
+/// The room replaced by another one, so its history can be linked in the timeline.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomPredecessor {
+    pub room_id: OwnedRoomId,
+    pub is_joined: bool,
+    /// The tombstone closing the predecessor history, to open its timeline where it ends.
+    /// `None` if the predecessor isn't joined.
+    pub tombstone_event_id: Option<OwnedEventId>,
+}
+
+/// A room version supported by the homeserver.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomVersionInfo {
+    pub version: RoomVersionId,
+    pub is_stable: bool,
+    pub is_default: bool,
+}
+
+pub async fn get_supported_room_versions() -> anyhow::Result<Vec<RoomVersionInfo>> {
+    let room_versions = get_client()?
+        .send(get_capabilities::v3::Request::new())
+        .await?
+        .capabilities
+        .room_versions;
+    Ok(room_versions
+        .available
+        .into_iter()
+        .map(|(version, stability)| RoomVersionInfo {
+            is_default: version == room_versions.default,
+            is_stable: stability == RoomVersionStability::Stable,
+            version,
+        })
+        .collect())
+}
+
+/// Upgrade a room to a new version supported by the homeserver, then invite again
+/// the users that had a pending invite in the old room.
+pub async fn upgrade_room(
+    room_id: &RoomId,
+    new_version: &str,
+) -> anyhow::Result<RoomUpgradeResult> {
+    let client = get_client()?;
+    let room = get_room(room_id)?;
+
+    let new_version = RoomVersionId::try_from(new_version)?;
+    let capabilities = client
+        .send(get_capabilities::v3::Request::new())
+        .await?
+        .capabilities;
+    if !capabilities
+        .room_versions
+        .available
+        .contains_key(&new_version)
+    {
+        return Err(anyhow!(
+            "The homeserver doesn't support room version {new_version}"
+        ));
+    }
+
+    let power_levels = room.power_levels().await?;
+    if !power_levels.user_can_send_state(room.own_user_id(), StateEventType::RoomTombstone) {
+        return Err(anyhow!("You are not allowed to upgrade this room"));
+    }
+
+    let invited: Vec<OwnedUserId> = room
+        .members(RoomMemberships::INVITE)
+        .await?
+        .into_iter()
+        .map(|m| m.user_id().to_owned())
+        .collect();
+
+    let new_room_id = client
+        .send(upgrade_room::v3::Request::new(
+            room_id.to_owned(),
+            new_version,
+        ))
+        .await?
+        .replacement_room;
+
+    let Ok(new_room) = tokio::time::timeout(
+        NEW_ROOM_TIMEOUT,
+        client.await_room_remote_echo(&new_room_id),
+    )
+    .await
+    else {
+        // The upgrade itself succeeded, only the invites couldn't be sent.
+        warn!("Upgraded room {new_room_id} didn't come down the sync in time");
+        return Ok(RoomUpgradeResult {
+            new_room_id,
+            failed_invites: invited,
+        });
+    };
+    let mut failed_invites = Vec::new();
+    for user_id in invited {
+        if let Err(e) = new_room.invite_user_by_id(&user_id).await {
+            warn!("Couldn't invite {user_id} in upgraded room {new_room_id}. {e}");
+            failed_invites.push(user_id);
+        }
+    }
+
+    Ok(RoomUpgradeResult {
+        new_room_id,
+        failed_invites,
+    })
+}
+
+/// Join the room that replaced a tombstoned room.
+/// Returns the id of the successor room.
+pub async fn follow_tombstone(room_id: &RoomId) -> anyhow::Result<OwnedRoomId> {
+    let client = get_client()?;
+    let room = get_room(room_id)?;
+
+    let tombstone =
+        get_state_event::<RoomTombstoneEventContent>(&room, StateEventType::RoomTombstone)
+            .await?
+            .ok_or(anyhow!("This room hasn't been replaced"))?;
+    let successor_id = tombstone.content.replacement_room;
+
+    if let Some(successor) = client.get_room(&successor_id)
+        && successor.state() == RoomState::Joined
+    {
+        return Ok(successor_id);
+    }
+
+    // The server that sent the tombstone is in the successor room,
+    // as well as the one of the successor room id, if any.
+    let mut via: Vec<OwnedServerName> = vec![tombstone.sender.server_name().to_owned()];
+    if let Some(server) = successor_id.server_name()
+        && !via.iter().any(|s| s == server)
+    {
+        via.push(server.to_owned());
+    }
+
+    client
+        .join_room_by_id_or_alias(<&RoomOrAliasId>::from(&*successor_id), &via)
+        .await?;
+    Ok(successor_id)
+}
+
+/// Get the room this room replaces, if any.
+pub async fn get_room_predecessor(room_id: &RoomId) -> anyhow::Result<Option<RoomPredecessor>> {
+    let client = get_client()?;
+    let room = get_room(room_id)?;
+
+    let Some(create) =
+        get_state_event::<RoomCreateEventContent>(&room, StateEventType::RoomCreate).await?
+    else {
+        return Ok(None);
+    };
+    let Some(predecessor) = create.content.predecessor else {
+        return Ok(None);
+    };
+    let joined_predecessor = client
+        .get_room(&predecessor.room_id)
+        .filter(|r| r.state() == RoomState::Joined);
+    let tombstone_event_id = match &joined_predecessor {
+        Some(room) => tombstone_event_id(room).await?,
+        None => None,
+    };
+    Ok(Some(RoomPredecessor {
+        room_id: predecessor.room_id,
+        is_joined: joined_predecessor.is_some(),
+        tombstone_event_id,
+    }))
+}
+
+async fn tombstone_event_id(room: &Room) -> anyhow::Result<Option<OwnedEventId>> {
+    let Some(raw) = room
+        .get_state_event_static::<RoomTombstoneEventContent>()
+        .await?
+    else {
+        return Ok(None);
+    };
+    Ok(match raw.deserialize()? {
+        SyncOrStrippedState::Sync(SyncStateEvent::Original(event)) => Some(event.event_id),
+        _ => None,
+    })
+}
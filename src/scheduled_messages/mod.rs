@@ -0,0 +1,415 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use matrix_sdk::ruma::UInt;
+use matrix_sdk::ruma::events::room::message::RoomMessageEventContentWithoutRelation;
+use matrix_ui_serializable::commands::OwnedEventId;
+use matrix_ui_serializable::{CLIENT, OwnedRoomId};
+use mime_serde_shim::Wrapper as MimeWrapper;
+use rand::RngExt;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_svelte::ManagerExt;
+use tracing::{error, warn};
+
+use crate::commands::{AttachmentInfoDeserHelper, ThumbnailDeserHelper};
+use crate::state_updaters::SCHEDULED_MESSAGES_STORE_ID;
+use crate::utils::{add_relations, get_room, write_atomically};
+
+const QUEUE_FILE_NAME: &str = "scheduled_messages.json";
+const MEDIA_DIR_NAME: &str = "scheduled_media";
+const SCHEDULER_TICK: Duration = Duration::from_secs(5);
+
+/// A message waiting to be sent at a given time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledMessage {
+    /// Local identifier of this scheduled message.
+    pub id: String,
+    pub room_id: OwnedRoomId,
+    pub thread_root: Option<OwnedEventId>,
+    pub in_reply_to: Option<OwnedEventId>,
+    /// When the message should be sent, in milliseconds since the Unix epoch.
+    pub send_at: u64,
+    pub content: ScheduledMessageContent,
+    pub state: ScheduledMessageState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "kind"
+)]
+pub enum ScheduledMessageContent {
+    Text {
+        body: String,
+    },
+    /// The media and thumbnail buffers are stored next to the queue file, not in the queue
+    /// itself.
+    Media {
+        filename: String,
+        mime_type: MimeWrapper,
+        caption: Option<String>,
+        info: AttachmentInfoDeserHelper,
+        thumbnail: Option<ScheduledThumbnail>,
+    },
+}
+
+/// The metadata of a thumbnail, its bytes are stored in a file named after the message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledThumbnail {
+    pub content_type: MimeWrapper,
+    pub height: UInt,
+    pub width: UInt,
+    pub size: UInt,
+}
+
+impl ScheduledThumbnail {
+    /// Split a thumbnail received from the frontend into its metadata and its bytes.
+    pub fn split(thumbnail: ThumbnailDeserHelper) -> (Self, Vec<u8>) {
+        let metadata = Self {
+            content_type: thumbnail.content_type,
+            height: thumbnail.height,
+            width: thumbnail.width,
+            size: thumbnail.size,
+        };
+        (metadata, thumbnail.data)
+    }
+
+    fn with_data(self, data: Vec<u8>) -> ThumbnailDeserHelper {
+        ThumbnailDeserHelper {
+            data,
+            content_type: self.content_type,
+            height: self.height,
+            width: self.width,
+            size: self.size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "state", content = "reason")]
+pub enum ScheduledMessageState {
+    Pending,
+    Sending,
+    Failed(String),
+}
+
+/// Persisted queue of messages to be sent later. It is managed by Tauri and
+/// survives app restarts through a JSON file in the app data directory.
+pub struct ScheduledMessagesQueue {
+    dir: PathBuf,
+    items: Mutex<Vec<ScheduledMessage>>,
+}
+
+impl ScheduledMessagesQueue {
+    pub fn load(app_data_dir: PathBuf) -> Self {
+        let mut items: Vec<ScheduledMessage> =
+            match std::fs::read_to_string(app_data_dir.join(QUEUE_FILE_NAME)) {
+                Ok(raw) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+                    error!("Couldn't deserialize scheduled messages queue. {e}");
+                    Vec::new()
+                }),
+                Err(_) => Vec::new(),
+            };
+        // Messages that were being sent when the app was closed are retried.
+        for item in items.iter_mut() {
+            if let ScheduledMessageState::Sending = item.state {
+                item.state = ScheduledMessageState::Pending;
+            }
+        }
+        Self {
+            dir: app_data_dir,
+            items: Mutex::new(items),
+        }
+    }
+
+    fn media_path(&self, id: &str) -> PathBuf {
+        self.dir.join(MEDIA_DIR_NAME).join(id)
+    }
+
+    fn thumbnail_path(&self, id: &str) -> PathBuf {
+        self.media_path(id).with_extension("thumbnail")
+    }
+
+    /// Write the queue to a temporary file first, so a crash can't leave a truncated queue.
+    fn persist(&self, items: &[ScheduledMessage]) -> anyhow::Result<()> {
+        write_atomically(
+            &self.dir.join(QUEUE_FILE_NAME),
+            serde_json::to_string(items)?,
+        )
+    }
+
+    /// Persists the queue and mirrors it into the frontend store.
+    fn commit<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        items: &[ScheduledMessage],
+    ) -> anyhow::Result<()> {
+        self.persist(items)?;
+        app_handle.svelte().set(
+            SCHEDULED_MESSAGES_STORE_ID,
+            "messages",
+            serde_json::to_value(items)?,
+        )?;
+        Ok(())
+    }
+
+    pub fn push<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        mut message: ScheduledMessage,
+        media_buffer: Option<Vec<u8>>,
+        thumbnail_buffer: Option<Vec<u8>>,
+    ) -> anyhow::Result<String> {
+        message.id = rand::rng()
+            .sample_iter(&rand::distr::Alphanumeric)
+            .take(16)
+            .map(char::from)
+            .collect();
+        let id = message.id.clone();
+        let mut items = self.items.lock().expect("Failed to lock mutex");
+        if let Some(buffer) = media_buffer
+            && let Err(e) = self.write_media(&id, buffer, thumbnail_buffer)
+        {
+            self.remove_media(&message);
+            return Err(e);
+        }
+        items.push(message);
+        items.sort_by_key(|m| m.send_at);
+        if let Err(e) = self.commit(app_handle, &items) {
+            let position = items
+                .iter()
+                .position(|m| m.id == id)
+                .expect("the message was just pushed");
+            let removed = items.remove(position);
+            self.remove_media(&removed);
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    fn write_media(
+        &self,
+        id: &str,
+        buffer: Vec<u8>,
+        thumbnail_buffer: Option<Vec<u8>>,
+    ) -> anyhow::Result<()> {
+        std::fs::create_dir_all(self.dir.join(MEDIA_DIR_NAME))?;
+        std::fs::write(self.media_path(id), buffer)?;
+        if let Some(thumbnail_buffer) = thumbnail_buffer {
+            std::fs::write(self.thumbnail_path(id), thumbnail_buffer)?;
+        }
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<ScheduledMessage> {
+        self.items.lock().expect("Failed to lock mutex").clone()
+    }
+
+    /// Updates the send time and/or the body (or caption for media) of a
+    /// message that is not being sent yet. A failed message is set back to pending.
+    pub fn edit<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        id: &str,
+        send_at: Option<u64>,
+        body: Option<String>,
+    ) -> anyhow::Result<()> {
+        let mut items = self.items.lock().expect("Failed to lock mutex");
+        let item = items
+            .iter_mut()
+            .find(|m| m.id == id)
+            .ok_or(anyhow!("No scheduled message with id {id}"))?;
+        if let ScheduledMessageState::Sending = item.state {
+            return Err(anyhow!("This message is already being sent"));
+        }
+        if let Some(send_at) = send_at {
+            item.send_at = send_at;
+        }
+        if let Some(new_body) = body {
+            match &mut item.content {
+                ScheduledMessageContent::Text { body } => *body = new_body,
+                ScheduledMessageContent::Media { caption, .. } => {
+                    *caption = Some(new_body).filter(|c| !c.is_empty())
+                }
+            }
+        }
+        item.state = ScheduledMessageState::Pending;
+        items.sort_by_key(|m| m.send_at);
+        self.commit(app_handle, &items)
+    }
+
+    pub fn cancel<R: Runtime>(&self, app_handle: &AppHandle<R>, id: &str) -> anyhow::Result<()> {
+        let mut items = self.items.lock().expect("Failed to lock mutex");
+        let position = items
+            .iter()
+            .position(|m| m.id == id)
+            .ok_or(anyhow!("No scheduled message with id {id}"))?;
+        if let ScheduledMessageState::Sending = items[position].state {
+            return Err(anyhow!("This message is already being sent"));
+        }
+        let removed = items.remove(position);
+        self.remove_media(&removed);
+        self.commit(app_handle, &items)
+    }
+
+    fn remove_media(&self, message: &ScheduledMessage) {
+        let ScheduledMessageContent::Media { thumbnail, .. } = &message.content else {
+            return;
+        };
+        if let Err(e) = std::fs::remove_file(self.media_path(&message.id)) {
+            warn!("Couldn't remove scheduled media file. {e}");
+        }
+        if thumbnail.is_some()
+            && let Err(e) = std::fs::remove_file(self.thumbnail_path(&message.id))
+        {
+            warn!("Couldn't remove scheduled thumbnail file. {e}");
+        }
+    }
+
+    /// Marks all due pending messages as being sent and returns them. They are set back to
+    /// pending if the queue can't be saved, and on load after a crash.
+    fn take_due<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+    ) -> anyhow::Result<Vec<ScheduledMessage>> {
+        let now = now_millis();
+        let mut items = self.items.lock().expect("Failed to lock mutex");
+        let mut due = Vec::new();
+        for item in items.iter_mut() {
+            if let ScheduledMessageState::Pending = item.state
+                && item.send_at <= now
+            {
+                item.state = ScheduledMessageState::Sending;
+                due.push(item.clone());
+            }
+        }
+        if !due.is_empty()
+            && let Err(e) = self.commit(app_handle, &items)
+        {
+            for item in items.iter_mut() {
+                if due.iter().any(|m| m.id == item.id) {
+                    item.state = ScheduledMessageState::Pending;
+                }
+            }
+            return Err(e);
+        }
+        Ok(due)
+    }
+
+    fn finish<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        id: &str,
+        result: anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let mut items = self.items.lock().expect("Failed to lock mutex");
+        let Some(position) = items.iter().position(|m| m.id == id) else {
+            return Ok(());
+        };
+        match result {
+            Ok(()) => {
+                let sent = items.remove(position);
+                self.remove_media(&sent);
+            }
+            Err(e) => {
+                error!("Couldn't send scheduled message. {e}");
+                items[position].state = ScheduledMessageState::Failed(e.to_string());
+            }
+        }
+        self.commit(app_handle, &items)
+    }
+
+    async fn send(&self, message: ScheduledMessage) -> anyhow::Result<()> {
+        match message.content {
+            ScheduledMessageContent::Text { body } => {
+                let room = get_room(&message.room_id)?;
+                let content = add_relations(
+                    &room,
+                    RoomMessageEventContentWithoutRelation::text_markdown(body),
+                    message.thread_root,
+                    message.in_reply_to,
+                )
+                .await?;
+                // Through the send queue, so a failure shows up in the outbox and can be retried.
+                room.send_queue().send(content.into()).await?;
+                Ok(())
+            }
+            ScheduledMessageContent::Media {
+                filename,
+                mime_type,
+                caption,
+                info,
+                thumbnail,
+            } => {
+                let buffer = std::fs::read(self.media_path(&message.id))?;
+                let thumbnail = match thumbnail {
+                    Some(thumbnail) => {
+                        let data = std::fs::read(self.thumbnail_path(&message.id))?;
+                        Some(thumbnail.with_data(data))
+                    }
+                    None => None,
+                };
+                matrix_ui_serializable::commands::send_media_message(
+                    message.room_id,
+                    message.thread_root,
+                    buffer,
+                    filename,
+                    mime_type.into(),
+                    caption,
+                    message.in_reply_to,
+                    info.into(),
+                    thumbnail.map(Into::into),
+                )
+                .await
+                .map_err(anyhow::Error::from)
+            }
+        }
+    }
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Background task that sends due messages. It is started once in the plugin setup.
+pub async fn run_scheduler<R: Runtime>(app_handle: AppHandle<R>) {
+    let queue = app_handle.state::<ScheduledMessagesQueue>();
+    // Mirror the persisted queue once the frontend is able to read it.
+    if let Err(e) = queue.commit(&app_handle, &queue.list()) {
+        warn!("Couldn't mirror scheduled messages to the store. {e}");
+    }
+
+    loop {
+        tokio::time::sleep(SCHEDULER_TICK).await;
+
+        // Nothing can be sent until the user is logged in.
+        if CLIENT.get().is_none() {
+            continue;
+        }
+
+        let due = match queue.take_due(&app_handle) {
+            Ok(due) => due,
+            Err(e) => {
+                error!("Couldn't update scheduled messages queue. {e}");
+                continue;
+            }
+        };
+
+        for message in due {
+            let id = message.id.clone();
+            let result = queue.send(message).await;
+            if let Err(e) = queue.finish(&app_handle, &id, result) {
+                error!("Couldn't update scheduled messages queue. {e}");
+            }
+        }
+    }
+}
@@ -0,0 +1,392 @@
+use anyhow::anyhow;
+use matrix_sdk::Room;
+use matrix_sdk::ruma::events::room::message::{
+    EmoteMessageEventContent, MessageType, RoomMessageEventContentWithoutRelation,
+};
+use matrix_sdk::ruma::events::{MessageLikeEventType, StateEventType};
+use matrix_sdk::ruma::{
+    OwnedEventId, OwnedServerName, OwnedUserId, RoomId, RoomOrAliasId, ServerName, UserId,
+};
+use matrix_ui_serializable::MatrixRequest;
+use matrix_ui_serializable::models::misc::SendTextMessagePayload;
+use serde::Serialize;
+
+use crate::utils::{add_relations, get_client};
+
+const SHRUG: &str = "¯\\_(ツ)_/¯";
+
+/// What happened to the text typed in the composer.
+#[derive(Debug, Serialize)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "kind"
+)]
+pub enum ComposerInputOutcome {
+    /// The input has been sent as a text message.
+    Sent,
+    /// A slash command has been executed.
+    Executed {
+        command: String,
+    },
+    /// A slash command has been submitted to the background request handler.
+    /// Its result isn't known yet.
+    Queued {
+        command: String,
+    },
+    UnknownCommand {
+        command: String,
+    },
+    InvalidArguments {
+        command: String,
+        usage: &'static str,
+    },
+    /// The current user isn't allowed to do this in the room.
+    /// `command` is `None` when the input was to be sent as a text message.
+    MissingPermission {
+        command: Option<String>,
+    },
+}
+
+/// The context of the composer, used when the input is sent as a message.
+pub struct ComposerContext {
+    pub thread_root: Option<OwnedEventId>,
+    pub in_reply_to: Option<OwnedEventId>,
+}
+
+enum Permission {
+    Message,
+    Invite,
+    Kick(OwnedUserId),
+    Ban(OwnedUserId),
+    State(StateEventType),
+}
+
+/// Parses and executes a slash command, or sends the input as a text message
+/// if it isn't one. A leading `//` sends the text starting with a single `/`.
+pub async fn execute_composer_input(
+    room_id: &RoomId,
+    text: &str,
+    context: ComposerContext,
+) -> anyhow::Result<ComposerInputOutcome> {
+    let room = get_client()?
+        .get_room(room_id)
+        .ok_or(anyhow!("Room {room_id} not found"))?;
+
+    let (command, args) = match parse_input(text) {
+        ComposerInput::Message(body) => {
+            return send_text(&room, None, body.to_owned(), context).await;
+        }
+        ComposerInput::Command { command, args } => (command, args),
+    };
+
+    let invalid = |usage| {
+        Ok(ComposerInputOutcome::InvalidArguments {
+            command: command.clone(),
+            usage,
+        })
+    };
+
+    match command.as_str() {
+        "me" => {
+            if args.is_empty() {
+                return invalid("/me <message>");
+            }
+            if !can(&room, Permission::Message).await? {
+                return missing_permission(command);
+            }
+            send_emote(&room, args, context).await?
+        }
+        "shrug" => {
+            let body = if args.is_empty() {
+                SHRUG.to_owned()
+            } else {
+                format!("{SHRUG} {args}")
+            };
+            return send_text(&room, Some(command), body, context).await;
+        }
+        "join" => {
+            let mut args = args.split_whitespace();
+            let Some(Ok(room_or_alias_id)) = args.next().map(RoomOrAliasId::parse) else {
+                return invalid("/join <#alias:server | !room:server> [via servers…]");
+            };
+            let Ok(via) = args
+                .map(ServerName::parse)
+                .collect::<Result<Vec<OwnedServerName>, _>>()
+            else {
+                return invalid("/join <#alias:server | !room:server> [via servers…]");
+            };
+            return queue(
+                command,
+                MatrixRequest::JoinRoom {
+                    room_or_alias_id,
+                    via: (!via.is_empty()).then_some(via),
+                },
+            );
+        }
+        "leave" | "part" => {
+            return queue(
+                command,
+                MatrixRequest::LeaveRoom {
+                    room_id: room_id.to_owned(),
+                },
+            );
+        }
+        "invite" => {
+            let Some(user_ids) = parse_user_ids(args) else {
+                return invalid("/invite <@user:server> [@other:server…]");
+            };
+            if !can(&room, Permission::Invite).await? {
+                return missing_permission(command);
+            }
+            return queue(
+                command,
+                MatrixRequest::InviteUsersInRoom {
+                    room_id: room_id.to_owned(),
+                    invited_user_ids: user_ids,
+                },
+            );
+        }
+        "kick" | "ban" => {
+            let is_ban = command == "ban";
+            let Some((user_id, reason)) = parse_user_and_reason(args) else {
+                return invalid(if is_ban {
+                    "/ban <@user:server> [reason]"
+                } else {
+                    "/kick <@user:server> [reason]"
+                });
+            };
+            let permission = if is_ban {
+                Permission::Ban(user_id.clone())
+            } else {
+                Permission::Kick(user_id.clone())
+            };
+            if !can(&room, permission).await? {
+                return missing_permission(command);
+            }
+            return queue(
+                command,
+                MatrixRequest::KickOrBanUserFromRoom {
+                    room_id: room_id.to_owned(),
+                    user_id,
+                    reason,
+                    is_ban,
+                },
+            );
+        }
+        "unban" => {
+            let Some((user_id, reason)) = parse_user_and_reason(args) else {
+                return invalid("/unban <@user:server> [reason]");
+            };
+            if !can(&room, Permission::Ban(user_id.clone())).await? {
+                return missing_permission(command);
+            }
+            room.unban_user(&user_id, reason.as_deref()).await?;
+        }
+        "nick" => {
+            if args.is_empty() {
+                return invalid("/nick <display name>");
+            }
+            get_client()?.account().set_display_name(Some(args)).await?;
+        }
+        "myroomnick" => {
+            if args.is_empty() {
+                return invalid("/myroomnick <display name>");
+            }
+            set_room_display_name(&room, args).await?;
+        }
+        "topic" => {
+            if args.is_empty() {
+                return invalid("/topic <topic>");
+            }
+            if !can(&room, Permission::State(StateEventType::RoomTopic)).await? {
+                return missing_permission(command);
+            }
+            room.set_room_topic(args).await?;
+        }
+        "roomname" => {
+            if args.is_empty() {
+                return invalid("/roomname <name>");
+            }
+            if !can(&room, Permission::State(StateEventType::RoomName)).await? {
+                return missing_permission(command);
+            }
+            room.set_name(args.to_owned()).await?;
+        }
+        _ => return Ok(ComposerInputOutcome::UnknownCommand { command }),
+    }
+    Ok(ComposerInputOutcome::Executed { command })
+}
+
+/// The text typed in the composer, once the slash command prefix has been looked for.
+#[derive(Debug, PartialEq)]
+enum ComposerInput<'a> {
+    Message(&'a str),
+    Command { command: String, args: &'a str },
+}
+
+fn parse_input(text: &str) -> ComposerInput<'_> {
+    let Some(input) = text.strip_prefix('/').filter(|t| !t.starts_with('/')) else {
+        return ComposerInput::Message(text.strip_prefix('/').unwrap_or(text));
+    };
+    let (command, args) = input
+        .split_once(char::is_whitespace)
+        .map_or((input, ""), |(command, args)| (command, args.trim()));
+    ComposerInput::Command {
+        command: command.to_lowercase(),
+        args,
+    }
+}
+
+fn missing_permission(command: String) -> anyhow::Result<ComposerInputOutcome> {
+    Ok(ComposerInputOutcome::MissingPermission {
+        command: Some(command),
+    })
+}
+
+async fn can(room: &Room, permission: Permission) -> anyhow::Result<bool> {
+    let own_user_id = room.own_user_id();
+    let power_levels = room.power_levels().await?;
+    Ok(match permission {
+        Permission::Message => {
+            power_levels.user_can_send_message(own_user_id, MessageLikeEventType::RoomMessage)
+        }
+        Permission::Invite => power_levels.user_can_invite(own_user_id),
+        Permission::Kick(target) => power_levels.user_can_kick_user(own_user_id, &target),
+        Permission::Ban(target) => power_levels.user_can_ban_user(own_user_id, &target),
+        Permission::State(event_type) => power_levels.user_can_send_state(own_user_id, event_type),
+    })
+}
+
+fn parse_user_ids(args: &str) -> Option<Vec<OwnedUserId>> {
+    let user_ids: Vec<OwnedUserId> = args
+        .split_whitespace()
+        .map(UserId::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    (!user_ids.is_empty()).then_some(user_ids)
+}
+
+fn parse_user_and_reason(args: &str) -> Option<(OwnedUserId, Option<String>)> {
+    let (user_id, reason) = args
+        .split_once(char::is_whitespace)
+        .map_or((args, ""), |(user_id, reason)| (user_id, reason.trim()));
+    let user_id = UserId::parse(user_id).ok()?;
+    Some((user_id, (!reason.is_empty()).then(|| reason.to_owned())))
+}
+
+/// Submit a request to the same handler as the ones sent by the frontend.
+/// It runs in the background, so the command is only reported as queued.
+fn queue(command: String, request: MatrixRequest) -> anyhow::Result<ComposerInputOutcome> {
+    matrix_ui_serializable::commands::submit_async_request(request);
+    Ok(ComposerInputOutcome::Queued { command })
+}
+
+async fn send_text(
+    room: &Room,
+    command: Option<String>,
+    body: String,
+    context: ComposerContext,
+) -> anyhow::Result<ComposerInputOutcome> {
+    if !can(room, Permission::Message).await? {
+        return Ok(ComposerInputOutcome::MissingPermission { command });
+    }
+    matrix_ui_serializable::commands::submit_async_request(MatrixRequest::SendTextMessage(
+        SendTextMessagePayload {
+            room_id: room.room_id().to_owned(),
+            thread_root_event_id: context.thread_root,
+            message: body,
+            reply_to_id: context.in_reply_to,
+        },
+    ));
+    Ok(ComposerInputOutcome::Sent)
+}
+
+async fn send_emote(room: &Room, text: &str, context: ComposerContext) -> anyhow::Result<()> {
+    let formatted = crate::formatting::format_markdown(Some(room.room_id()), text).await?;
+    let mut emote = EmoteMessageEventContent::plain(formatted.body);
+    emote.formatted = formatted.formatted;
+    let content = add_relations(
+        room,
+        RoomMessageEventContentWithoutRelation::new(MessageType::Emote(emote))
+            .add_mentions(formatted.mentions),
+        context.thread_root,
+        context.in_reply_to,
+    )
+    .await?;
+    room.send_queue().send(content.into()).await?;
+    Ok(())
+}
+
+async fn set_room_display_name(room: &Room, name: &str) -> anyhow::Result<()> {
+    let own_user_id = room.own_user_id();
+    let member = room
+        .get_member_no_sync(own_user_id)
+        .await?
+        .ok_or(anyhow!("You are not a member of this room"))?;
+    let mut content = member
+        .event()
+        .original_content()
+        .ok_or(anyhow!("Couldn't get the current membership of this room"))?
+        .clone();
+    content.displayname = Some(name.to_owned());
+    room.send_state_event_for_key(own_user_id, content).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command<'a>(command: &str, args: &'a str) -> ComposerInput<'a> {
+        ComposerInput::Command {
+            command: command.to_owned(),
+            args,
+        }
+    }
+
+    #[test]
+    fn parses_a_command_and_its_arguments() {
+        assert_eq!(
+            parse_input("/ME  waves at everyone "),
+            command("me", "waves at everyone")
+        );
+        assert_eq!(parse_input("/leave"), command("leave", ""));
+    }
+
+    #[test]
+    fn sends_text_that_isnt_a_command() {
+        assert_eq!(
+            parse_input("hello /me"),
+            ComposerInput::Message("hello /me")
+        );
+        assert_eq!(parse_input("//me"), ComposerInput::Message("/me"));
+    }
+
+    #[test]
+    fn parses_user_ids() {
+        assert_eq!(
+            parse_user_ids("@alice:example.org  @bob:example.org"),
+            Some(vec![
+                UserId::parse("@alice:example.org").unwrap(),
+                UserId::parse("@bob:example.org").unwrap(),
+            ])
+        );
+        assert_eq!(parse_user_ids(""), None);
+        assert_eq!(parse_user_ids("@alice:example.org bob"), None);
+    }
+
+    #[test]
+    fn parses_a_user_and_a_reason() {
+        let alice = UserId::parse("@alice:example.org").unwrap();
+        assert_eq!(
+            parse_user_and_reason("@alice:example.org  spamming links "),
+            Some((alice.clone(), Some("spamming links".to_owned())))
+        );
+        assert_eq!(
+            parse_user_and_reason("@alice:example.org"),
+            Some((alice, None))
+        );
+        assert_eq!(parse_user_and_reason("alice spamming"), None);
+    }
+}
@@ -8,18 +8,43 @@ use matrix_ui_serializable::{
     models::state_updater::{StateUpdater, StateUpdaterFunctions},
 };
 use serde_json::Value;
-use tauri::{AppHandle, Runtime};
+use tauri::{AppHandle, Manager, Runtime};
 use tauri_plugin_svelte::{ManagerExt, StoreState};
 
 use crate::{
+    autocomplete::AutocompleteCache,
+    formatting::sanitize::{sanitize_room_screen, sanitize_rooms_list},
+    ignored_users::IgnoredUsers,
+    invites,
     keyring::{get_matrix_session_option, set_session_in_keyring},
+    outbox,
+    presence::PresenceTracker,
+    user_verification::trust::MemberTrust,
     utils::get_app_dir_or_create_it,
 };
 
 // Keep the same ids as in the JS package !
-const ROOM_STORE_ID: &str = "room-store";
-const ROOMS_COLLECTION_STORE_ID: &str = "rooms-collection";
+pub(crate) const ROOM_STORE_ID: &str = "room-store";
+pub(crate) const ROOMS_COLLECTION_STORE_ID: &str = "rooms-collection";
 pub const LOGIN_STATE_STORE_ID: &str = "login-state";
+pub(crate) const SCHEDULED_MESSAGES_STORE_ID: &str = "scheduled-messages";
+
+/// Patch a store with the top-level fields of a serialized object.
+pub(crate) fn patch_store<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    store_id: &str,
+    json: Value,
+) -> anyhow::Result<()> {
+    let Value::Object(map) = json else {
+        return Err(anyhow!(
+            "Unexpected JSON object received during serialization"
+        ));
+    };
+    let mut state = StoreState::new();
+    state.patch(map.into_iter().collect::<HashMap<String, Value>>());
+    app_handle.svelte().patch(store_id, state)?;
+    Ok(())
+}
 
 #[derive(Debug)]
 pub struct Updaters<R: Runtime> {
@@ -35,7 +60,11 @@ impl<R: Runtime> Updaters<R> {
 #[async_trait]
 impl<R: Runtime> StateUpdaterFunctions for Updaters<R> {
     fn update_rooms_list(&self, rooms_list: &RoomsList) -> anyhow::Result<()> {
-        let json = serde_json::to_value(rooms_list).expect("Couldn't serialize Rooms List");
+        let mut json = serde_json::to_value(rooms_list).expect("Couldn't serialize Rooms List");
+        sanitize_rooms_list(&mut json);
+        self.app_handle
+            .state::<IgnoredUsers>()
+            .filter_rooms_list(&mut json);
         let mut empty_state = StoreState::new();
         let state = match json {
             Value::Object(map) => {
@@ -54,7 +83,21 @@ impl<R: Runtime> StateUpdaterFunctions for Updaters<R> {
         Ok(())
     }
     fn update_room(&self, room: &RoomScreen) -> anyhow::Result<()> {
-        let json = serde_json::to_value(room).expect("Couldn't serialize Rooms List");
+        let mut json = serde_json::to_value(room).expect("Couldn't serialize Rooms List");
+        // Formatted bodies from other users must not reach the webview as is.
+        sanitize_room_screen(&mut json);
+        self.app_handle
+            .state::<IgnoredUsers>()
+            .filter_room_screen(&mut json);
+        self.app_handle
+            .state::<AutocompleteCache>()
+            .update_from_room_screen(&json);
+        self.app_handle
+            .state::<PresenceTracker>()
+            .update_from_room_screen(&json);
+        self.app_handle
+            .state::<MemberTrust>()
+            .annotate_room_screen(&mut json);
         let mut empty_state = StoreState::new();
         let state = match json {
             Value::Object(map) => {
@@ -76,6 +119,10 @@ impl<R: Runtime> StateUpdaterFunctions for Updaters<R> {
         &self,
         sync_service_state: FrontendSyncServiceState,
     ) -> anyhow::Result<()> {
+        if let FrontendSyncServiceState::Running = sync_service_state {
+            outbox::on_sync_service_running(&self.app_handle);
+            invites::on_sync_service_running(&self.app_handle);
+        }
         self.app_handle.svelte().set(
             LOGIN_STATE_STORE_ID,
             "syncServiceState",
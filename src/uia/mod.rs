@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use matrix_sdk::ruma::api::client::uiaa::{AuthData, UiaaInfo};
+use rand::RngExt;
+use serde::Serialize;
+use serde_json::{Value, json};
+use url::Url;
+
+use crate::utils::get_client;
+
+const LOCAL_SESSION_LENGTH: usize = 16;
+/// Pending operations are forgotten after this delay, so abandoned ones don't pile up.
+const PENDING_OPERATION_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// The outcome of an operation protected by user-interactive authentication.
+#[derive(Debug, Serialize)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "kind"
+)]
+pub enum UiaOutcome {
+    Done {
+        result: Value,
+    },
+    /// The operation continues with `continue_uia(session, auth)`, `auth` being
+    /// the auth object of a stage of one of the flows.
+    UiaRequired {
+        session: String,
+        /// Each flow is a list of stage types, i.e. `m.login.password`.
+        flows: Vec<Vec<String>>,
+        /// The stages already completed in this session.
+        completed: Vec<String>,
+        /// The parameters of each stage, by stage type.
+        params: Value,
+        /// The error of the last attempted stage, if any.
+        error: Option<String>,
+    },
+}
+
+/// The error of an operation protected by user-interactive authentication.
+pub enum UiaError {
+    /// The homeserver requires (more) authentication.
+    Required(UiaaInfo),
+    Other(anyhow::Error),
+}
+
+impl From<matrix_sdk::HttpError> for UiaError {
+    fn from(e: matrix_sdk::HttpError) -> Self {
+        match e.as_uiaa_response() {
+            Some(info) => Self::Required(info.clone()),
+            None => Self::Other(e.into()),
+        }
+    }
+}
+
+impl From<matrix_sdk::Error> for UiaError {
+    fn from(e: matrix_sdk::Error) -> Self {
+        match e.as_uiaa_response() {
+            Some(info) => Self::Required(info.clone()),
+            None => Self::Other(e.into()),
+        }
+    }
+}
+
+impl From<anyhow::Error> for UiaError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Other(e)
+    }
+}
+
+type UiaOperation =
+    Arc<dyn Fn(Option<AuthData>) -> BoxFuture<'static, Result<Value, UiaError>> + Send + Sync>;
+
+struct PendingOperation {
+    operation: UiaOperation,
+    /// The session given by the homeserver, if any.
+    server_session: Option<String>,
+    expires_at: Instant,
+}
+
+/// The operations waiting for the frontend to complete an authentication stage, by session.
+#[derive(Default)]
+pub struct UiaSessions {
+    pending: Mutex<HashMap<String, PendingOperation>>,
+}
+
+impl UiaSessions {
+    /// Run an operation, without authentication at first.
+    /// It is called again with the auth data given to `continue_uia`, until it succeeds.
+    pub async fn start<T, F, Fut>(&self, operation: F) -> anyhow::Result<UiaOutcome>
+    where
+        T: Serialize,
+        F: Fn(Option<AuthData>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<T, UiaError>> + Send + 'static,
+    {
+        let operation: UiaOperation = Arc::new(move |auth| {
+            let future = operation(auth);
+            async move {
+                let result = future.await?;
+                serde_json::to_value(result).map_err(|e| UiaError::Other(e.into()))
+            }
+            .boxed()
+        });
+        self.run(operation, None, None).await
+    }
+
+    /// Continue an operation with the auth data of a stage. The session is set by us.
+    /// The operation is forgotten if it fails with another error than a new stage to complete.
+    pub async fn continue_uia(&self, session: &str, auth: Value) -> anyhow::Result<UiaOutcome> {
+        let PendingOperation {
+            operation,
+            server_session,
+            ..
+        } = {
+            let mut pending = self.pending.lock().expect("Failed to lock mutex");
+            remove_expired(&mut pending);
+            pending.remove(session).ok_or(anyhow!(
+                "No pending authentication for session {session}, it may have expired"
+            ))?
+        };
+        let auth = parse_auth(auth, server_session.as_deref())?;
+        self.run(operation, Some(auth), Some(session.to_owned()))
+            .await
+    }
+
+    /// Forget a pending operation, i.e. when the user cancels it.
+    pub fn cancel(&self, session: &str) {
+        self.pending
+            .lock()
+            .expect("Failed to lock mutex")
+            .remove(session);
+    }
+
+    async fn run(
+        &self,
+        operation: UiaOperation,
+        auth: Option<AuthData>,
+        local_session: Option<String>,
+    ) -> anyhow::Result<UiaOutcome> {
+        match operation(auth).await {
+            Ok(result) => Ok(UiaOutcome::Done { result }),
+            Err(UiaError::Required(info)) => {
+                // The homeserver session is used when there is one, so the frontend can build
+                // the fallback URL of a stage from it.
+                let session = info
+                    .session
+                    .clone()
+                    .or(local_session)
+                    .unwrap_or_else(local_session_id);
+                let mut pending = self.pending.lock().expect("Failed to lock mutex");
+                remove_expired(&mut pending);
+                pending.insert(
+                    session.clone(),
+                    PendingOperation {
+                        operation,
+                        server_session: info.session.clone(),
+                        expires_at: Instant::now() + PENDING_OPERATION_TTL,
+                    },
+                );
+                Ok(uia_required(session, &info))
+            }
+            Err(UiaError::Other(e)) => Err(e),
+        }
+    }
+}
+
+fn remove_expired(pending: &mut HashMap<String, PendingOperation>) {
+    let now = Instant::now();
+    pending.retain(|_, operation| operation.expires_at > now);
+}
+
+fn local_session_id() -> String {
+    rand::rng()
+        .sample_iter(&rand::distr::Alphanumeric)
+        .take(LOCAL_SESSION_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// The stage types of each flow.
+pub(crate) fn flows_of(info: &UiaaInfo) -> Vec<Vec<String>> {
+    info.flows
+        .iter()
+        .map(|flow| flow.stages.iter().map(ToString::to_string).collect())
+        .collect()
+}
+
+/// The parameters of each stage, by stage type.
+pub(crate) fn params_of(info: &UiaaInfo) -> Value {
+    info.params
+        .as_ref()
+        .and_then(|p| serde_json::from_str(p.get()).ok())
+        .unwrap_or_default()
+}
+
+fn uia_required(session: String, info: &UiaaInfo) -> UiaOutcome {
+    UiaOutcome::UiaRequired {
+        session,
+        flows: flows_of(info),
+        completed: info.completed.iter().map(ToString::to_string).collect(),
+        params: params_of(info),
+        error: info.auth_error.as_ref().map(|e| e.message.clone()),
+    }
+}
+
+/// Parse the auth object sent by the frontend, as defined in the spec.
+/// The session is set, and the password stage gets the current user as identifier if missing.
+fn parse_auth(mut auth: Value, server_session: Option<&str>) -> anyhow::Result<AuthData> {
+    let object = auth
+        .as_object_mut()
+        .ok_or(anyhow!("The authentication data must be an object"))?;
+    match server_session {
+        Some(session) => object.insert("session".to_owned(), json!(session)),
+        None => object.remove("session"),
+    };
+    if object.get("type").and_then(Value::as_str) == Some("m.login.password")
+        && !object.contains_key("identifier")
+        && let Some(user_id) = get_client().ok().and_then(|c| c.user_id())
+    {
+        object.insert(
+            "identifier".to_owned(),
+            json!({ "type": "m.id.user", "user": user_id }),
+        );
+    }
+    serde_json::from_value(auth).map_err(|e| anyhow!("Invalid authentication data. {e}"))
+}
+
+/// The URL of the web page completing a stage without native support (i.e. SSO or recaptcha).
+/// Once the page is done, call `continue_uia` with an auth object without type.
+pub fn get_fallback_url(homeserver: &Url, stage: &str, session: &str) -> anyhow::Result<String> {
+    let mut url = homeserver.join(&format!("_matrix/client/v3/auth/{stage}/fallback/web"))?;
+    url.query_pairs_mut().append_pair("session", session);
+    Ok(url.to_string())
+}
@@ -0,0 +1,353 @@
+use anyhow::anyhow;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use futures::StreamExt;
+use matrix_sdk::encryption::verification::{
+    QrVerification, QrVerificationData, QrVerificationState, SasState, SasVerification,
+    Verification, VerificationRequest, VerificationRequestState,
+};
+use matrix_sdk::ruma::events::key::verification::VerificationMethod;
+use matrix_sdk::ruma::events::room::message::{MessageType, OriginalSyncRoomMessageEvent};
+use matrix_sdk::ruma::{
+    MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, OwnedUserId, UserId,
+};
+use matrix_sdk::{Client, Room};
+use serde::Serialize;
+use tauri::ipc::Channel;
+use tauri::{AppHandle, Emitter, Runtime};
+use tracing::error;
+
+use crate::utils::{get_client, wait_for_client};
+
+pub mod trust;
+
+/// QR codes are offered along with SAS, the frontend picks what the other user supports.
+const SUPPORTED_METHODS: [VerificationMethod; 4] = [
+    VerificationMethod::SasV1,
+    VerificationMethod::QrCodeShowV1,
+    VerificationMethod::QrCodeScanV1,
+    VerificationMethod::ReciprocateV1,
+];
+
+/// Requests older than this are ignored, as per the spec.
+const REQUEST_TIMEOUT_MS: u64 = 10 * 60 * 1000;
+
+/// A verification request sent by another user in a DM.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IncomingVerificationRequest {
+    pub user_id: OwnedUserId,
+    /// The id of the request event, used to answer it.
+    pub flow_id: OwnedEventId,
+    pub room_id: OwnedRoomId,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SasEmoji {
+    pub symbol: String,
+    pub description: String,
+}
+
+/// The progress of the verification of another user, sent through a channel.
+#[derive(Debug, Clone, Serialize)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "event",
+    content = "data"
+)]
+pub enum UserVerificationEvent {
+    /// Waiting for the other user to accept the request.
+    Requested {
+        flow_id: String,
+    },
+    /// Both users accepted. The verification continues with `start_sas_verification`,
+    /// `get_user_verification_qr_code` or `scan_user_verification_qr_code`.
+    Ready {
+        can_show_qr_code: bool,
+        can_scan_qr_code: bool,
+    },
+    /// The emojis (if supported by both sides) and decimals to compare with the other user,
+    /// then to confirm with `confirm_user_verification`.
+    SasKeys {
+        emojis: Option<Vec<SasEmoji>>,
+        decimals: (u16, u16, u16),
+    },
+    /// The other user scanned our QR code. Confirm it once they show it succeeded.
+    QrCodeScanned,
+    Done,
+    Cancelled {
+        reason: String,
+    },
+}
+
+fn send(on_event: &Channel<UserVerificationEvent>, event: UserVerificationEvent) {
+    if let Err(e) = on_event.send(event) {
+        error!("Couldn't send the user verification progress. {e}");
+    }
+}
+
+async fn get_request(user_id: &UserId, flow_id: &str) -> anyhow::Result<VerificationRequest> {
+    get_client()?
+        .encryption()
+        .get_verification_request(user_id, flow_id)
+        .await
+        .ok_or(anyhow!("No verification request {flow_id} with {user_id}"))
+}
+
+async fn drive_sas(
+    sas: SasVerification,
+    on_event: &Channel<UserVerificationEvent>,
+) -> anyhow::Result<()> {
+    let mut changes = sas.changes();
+    if !sas.we_started() {
+        sas.accept().await?;
+    }
+    while let Some(state) = changes.next().await {
+        match state {
+            SasState::KeysExchanged { emojis, decimals } => {
+                let emojis = emojis.map(|e| {
+                    e.emojis
+                        .iter()
+                        .map(|emoji| SasEmoji {
+                            symbol: emoji.symbol.to_owned(),
+                            description: emoji.description.to_owned(),
+                        })
+                        .collect()
+                });
+                send(
+                    on_event,
+                    UserVerificationEvent::SasKeys { emojis, decimals },
+                );
+            }
+            SasState::Done { .. } => {
+                send(on_event, UserVerificationEvent::Done);
+                break;
+            }
+            SasState::Cancelled(info) => {
+                send(
+                    on_event,
+                    UserVerificationEvent::Cancelled {
+                        reason: info.reason().to_owned(),
+                    },
+                );
+                break;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+async fn drive_qr(qr: QrVerification, on_event: &Channel<UserVerificationEvent>) {
+    let mut changes = qr.changes();
+    while let Some(state) = changes.next().await {
+        match state {
+            QrVerificationState::Scanned => send(on_event, UserVerificationEvent::QrCodeScanned),
+            QrVerificationState::Done { .. } => {
+                send(on_event, UserVerificationEvent::Done);
+                break;
+            }
+            QrVerificationState::Cancelled(info) => {
+                send(
+                    on_event,
+                    UserVerificationEvent::Cancelled {
+                        reason: info.reason().to_owned(),
+                    },
+                );
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Report the progress of a request until the verification is done or cancelled.
+async fn drive_request(
+    request: VerificationRequest,
+    on_event: Channel<UserVerificationEvent>,
+) -> anyhow::Result<()> {
+    let mut changes = request.changes();
+    // The current state isn't part of the changes.
+    let mut next_state = Some(request.state());
+    loop {
+        let state = match next_state.take() {
+            Some(state) => state,
+            None => match changes.next().await {
+                Some(state) => state,
+                None => return Ok(()),
+            },
+        };
+        match state {
+            VerificationRequestState::Created { .. }
+            | VerificationRequestState::Requested { .. } => {}
+            VerificationRequestState::Ready { their_methods, .. } => send(
+                &on_event,
+                UserVerificationEvent::Ready {
+                    can_show_qr_code: their_methods.contains(&VerificationMethod::QrCodeScanV1),
+                    can_scan_qr_code: their_methods.contains(&VerificationMethod::QrCodeShowV1),
+                },
+            ),
+            VerificationRequestState::Transitioned { verification } => {
+                return match verification {
+                    Verification::SasV1(sas) => drive_sas(sas, &on_event).await,
+                    Verification::QrV1(qr) => {
+                        drive_qr(qr, &on_event).await;
+                        Ok(())
+                    }
+                    _ => Err(anyhow!("Unsupported verification method")),
+                };
+            }
+            VerificationRequestState::Done => {
+                send(&on_event, UserVerificationEvent::Done);
+                return Ok(());
+            }
+            VerificationRequestState::Cancelled(info) => {
+                send(
+                    &on_event,
+                    UserVerificationEvent::Cancelled {
+                        reason: info.reason().to_owned(),
+                    },
+                );
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Ask another user to verify their identity, through a `m.key.verification.request`
+/// in the DM with them. It returns once the verification is done or cancelled.
+pub async fn request_user_verification(
+    user_id: &UserId,
+    on_event: Channel<UserVerificationEvent>,
+) -> anyhow::Result<()> {
+    let identity = get_client()?
+        .encryption()
+        .get_user_identity(user_id)
+        .await?
+        .ok_or(anyhow!("{user_id} hasn't set up cross-signing"))?;
+    let request = identity
+        .request_verification_with_methods(SUPPORTED_METHODS.to_vec())
+        .await?;
+    send(
+        &on_event,
+        UserVerificationEvent::Requested {
+            flow_id: request.flow_id().to_owned(),
+        },
+    );
+    drive_request(request, on_event).await
+}
+
+/// Accept a request received from another user.
+/// It returns once the verification is done or cancelled.
+pub async fn accept_user_verification(
+    user_id: &UserId,
+    flow_id: &str,
+    on_event: Channel<UserVerificationEvent>,
+) -> anyhow::Result<()> {
+    let request = get_request(user_id, flow_id).await?;
+    request
+        .accept_with_methods(SUPPORTED_METHODS.to_vec())
+        .await?;
+    drive_request(request, on_event).await
+}
+
+pub async fn start_sas_verification(user_id: &UserId, flow_id: &str) -> anyhow::Result<()> {
+    get_request(user_id, flow_id)
+        .await?
+        .start_sas()
+        .await?
+        .ok_or(anyhow!("The verification can't use emojis or decimals"))?;
+    Ok(())
+}
+
+/// The QR code to show to the other user, as base64.
+pub async fn get_qr_code(user_id: &UserId, flow_id: &str) -> anyhow::Result<String> {
+    let qr = get_request(user_id, flow_id)
+        .await?
+        .generate_qr_code()
+        .await?
+        .ok_or(anyhow!("The other user can't scan QR codes"))?;
+    Ok(BASE64.encode(qr.to_bytes()?))
+}
+
+/// Scan the QR code shown by the other user, given as base64.
+pub async fn scan_qr_code(user_id: &UserId, flow_id: &str, qr_code: &str) -> anyhow::Result<()> {
+    let data = QrVerificationData::from_bytes(BASE64.decode(qr_code)?)?;
+    get_request(user_id, flow_id)
+        .await?
+        .scan_qr_code(data)
+        .await?
+        .ok_or(anyhow!("The verification can't use QR codes"))?;
+    Ok(())
+}
+
+/// Confirm that the emojis or decimals match, or that the other user scanned our QR code.
+/// Without `matches`, the verification is cancelled.
+pub async fn confirm_user_verification(
+    user_id: &UserId,
+    flow_id: &str,
+    matches: bool,
+) -> anyhow::Result<()> {
+    let verification = get_client()?
+        .encryption()
+        .get_verification(user_id, flow_id)
+        .await
+        .ok_or(anyhow!(
+            "The verification with {user_id} hasn't started yet"
+        ))?;
+    match (verification, matches) {
+        (Verification::SasV1(sas), true) => sas.confirm().await?,
+        (Verification::SasV1(sas), false) => sas.mismatch().await?,
+        (Verification::QrV1(qr), true) => qr.confirm().await?,
+        (Verification::QrV1(qr), false) => qr.cancel().await?,
+        _ => return Err(anyhow!("Unsupported verification method")),
+    }
+    Ok(())
+}
+
+pub async fn cancel_user_verification(user_id: &UserId, flow_id: &str) -> anyhow::Result<()> {
+    get_request(user_id, flow_id).await?.cancel().await?;
+    Ok(())
+}
+
+/// Emit the verification requests sent to us in DMs through
+/// `matrix-svelte://user-verification-request`. It is started once in the plugin setup.
+pub async fn run_verification_request_listener<R: Runtime>(app_handle: AppHandle<R>) {
+    let client = wait_for_client().await;
+    client.add_event_handler(
+        move |event: OriginalSyncRoomMessageEvent, room: Room, client: Client| {
+            let app_handle = app_handle.clone();
+            async move {
+                let MessageType::VerificationRequest(content) = &event.content.msgtype else {
+                    return;
+                };
+                let age = u64::from(MilliSecondsSinceUnixEpoch::now().get())
+                    .saturating_sub(event.origin_server_ts.get().into());
+                let is_expired = age > REQUEST_TIMEOUT_MS;
+                if client.user_id() != Some(&*content.to) || is_expired {
+                    return;
+                }
+                // A request replayed by the sync may already be answered or cancelled, and
+                // one the crypto store doesn't know can't be accepted anyway.
+                let is_pending = client
+                    .encryption()
+                    .get_verification_request(&event.sender, &event.event_id)
+                    .await
+                    .is_some_and(|request| !request.is_done() && !request.is_cancelled());
+                if !is_pending {
+                    return;
+                }
+                let request = IncomingVerificationRequest {
+                    user_id: event.sender,
+                    flow_id: event.event_id,
+                    room_id: room.room_id().to_owned(),
+                };
+                if let Err(e) =
+                    app_handle.emit("matrix-svelte://user-verification-request", request)
+                {
+                    error!("Couldn't emit the verification request. {e}");
+                }
+            }
+        },
+    );
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use matrix_sdk::Client;
+use matrix_sdk::ruma::UserId;
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_svelte::ManagerExt;
+use tracing::{error, warn};
+
+use crate::state_updaters::ROOM_STORE_ID;
+use crate::utils::wait_for_client;
+
+const TRUST_FIELD: &str = "trust";
+
+/// Whether the cross-signing identity of a user has been verified by the current user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TrustLevel {
+    Verified,
+    Unverified,
+    /// The user was verified, or pinned, and their identity changed since.
+    IdentityChanged,
+}
+
+/// The members of the last room screen, so they can be annotated again when trust changes.
+#[derive(Clone)]
+struct DisplayedMembers {
+    room_id: Option<String>,
+    members: Map<String, Value>,
+}
+
+/// The trust level of the members of the opened room, added to each member of the room store.
+pub struct MemberTrust {
+    levels: Mutex<HashMap<String, TrustLevel>>,
+    last_members: Mutex<Option<DisplayedMembers>>,
+    refresh_tx: mpsc::UnboundedSender<()>,
+    refresh_rx: Mutex<Option<mpsc::UnboundedReceiver<()>>>,
+}
+
+impl Default for MemberTrust {
+    fn default() -> Self {
+        let (refresh_tx, refresh_rx) = mpsc::unbounded();
+        Self {
+            levels: Mutex::new(HashMap::new()),
+            last_members: Mutex::new(None),
+            refresh_tx,
+            refresh_rx: Mutex::new(Some(refresh_rx)),
+        }
+    }
+}
+
+impl MemberTrust {
+    /// Returns whether the trust level of some members is still unknown.
+    fn annotate(&self, members: &mut Map<String, Value>) -> bool {
+        let levels = self.levels.lock().expect("Failed to lock mutex");
+        let mut has_unknown = false;
+        for (user_id, member) in members.iter_mut() {
+            let Some(member) = member.as_object_mut() else {
+                continue;
+            };
+            match levels.get(user_id) {
+                Some(level) => {
+                    member.insert(TRUST_FIELD.to_owned(), json!(level));
+                }
+                None => has_unknown = true,
+            }
+        }
+        has_unknown
+    }
+
+    /// Add the known trust level to the members of a serialized `RoomScreen`.
+    /// The unknown ones are computed in the background, then pushed to the room store.
+    pub fn annotate_room_screen(&self, room_screen: &mut Value) {
+        let room_id = room_screen
+            .pointer("/timelineKind/roomId")
+            .and_then(Value::as_str)
+            .map(ToOwned::to_owned);
+        let Some(members) = room_screen
+            .get_mut("members")
+            .and_then(Value::as_object_mut)
+        else {
+            return;
+        };
+        *self.last_members.lock().expect("Failed to lock mutex") = Some(DisplayedMembers {
+            room_id,
+            members: members.clone(),
+        });
+        if self.annotate(members) {
+            let _ = self.refresh_tx.unbounded_send(());
+        }
+    }
+}
+
+async fn trust_level(client: &Client, user_id: &UserId) -> anyhow::Result<TrustLevel> {
+    let Some(identity) = client.encryption().get_user_identity(user_id).await? else {
+        return Ok(TrustLevel::Unverified);
+    };
+    Ok(if identity.is_verified() {
+        TrustLevel::Verified
+    } else if identity.has_verification_violation() {
+        TrustLevel::IdentityChanged
+    } else {
+        TrustLevel::Unverified
+    })
+}
+
+/// Compute the trust level of the last members, and push them again if any changed.
+async fn refresh<R: Runtime>(app_handle: &AppHandle<R>, client: &Client) {
+    let trust = app_handle.state::<MemberTrust>();
+    let Some(displayed) = trust
+        .last_members
+        .lock()
+        .expect("Failed to lock mutex")
+        .clone()
+    else {
+        return;
+    };
+
+    let mut changed = false;
+    for user_id in displayed.members.keys() {
+        let Ok(user_id) = UserId::parse(user_id) else {
+            continue;
+        };
+        match trust_level(client, &user_id).await {
+            Ok(level) => {
+                let previous = trust
+                    .levels
+                    .lock()
+                    .expect("Failed to lock mutex")
+                    .insert(user_id.to_string(), level);
+                changed |= previous != Some(level);
+            }
+            Err(e) => warn!("Couldn't get the identity of {user_id}. {e}"),
+        }
+    }
+    if !changed {
+        return;
+    }
+    // Another room may have been opened while the identities were fetched, its members
+    // must not be replaced by the ones of the previous room.
+    let Some(mut members) = trust
+        .last_members
+        .lock()
+        .expect("Failed to lock mutex")
+        .as_ref()
+        .filter(|current| current.room_id == displayed.room_id)
+        .map(|current| current.members.clone())
+    else {
+        return;
+    };
+    trust.annotate(&mut members);
+    if let Err(e) = app_handle
+        .svelte()
+        .set(ROOM_STORE_ID, "members", Value::Object(members))
+    {
+        error!("Couldn't push the members trust to the room store. {e}");
+    }
+}
+
+/// Keep the trust level of the room members up to date, when new members are displayed
+/// or when identities change (i.e. after a verification). It is started once in the plugin setup.
+pub async fn run_member_trust_listener<R: Runtime>(app_handle: AppHandle<R>) {
+    let Some(refresh_rx) = app_handle
+        .state::<MemberTrust>()
+        .refresh_rx
+        .lock()
+        .expect("Failed to lock mutex")
+        .take()
+    else {
+        return;
+    };
+    let client = wait_for_client().await;
+    let identity_updates = match client.encryption().user_identities_stream().await {
+        Ok(stream) => stream.map(|_| ()),
+        Err(e) => {
+            error!("Couldn't listen to identity updates. {e}");
+            return;
+        }
+    };
+
+    let mut triggers = futures::stream::select(identity_updates.boxed(), refresh_rx);
+    while triggers.next().await.is_some() {
+        refresh(&app_handle, &client).await;
+    }
+}
@@ -1,10 +1,96 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use anyhow::anyhow;
+use matrix_sdk::deserialized_responses::RawAnySyncOrStrippedState;
+use matrix_sdk::room::reply::{EnforceThread, Reply};
+use matrix_sdk::ruma::events::StateEventType;
+use matrix_sdk::ruma::events::relation::Thread;
+use matrix_sdk::ruma::events::room::message::{
+    Relation, ReplyWithinThread, RoomMessageEventContent, RoomMessageEventContentWithoutRelation,
+};
+use matrix_sdk::ruma::{OwnedEventId, OwnedUserId, RoomId};
+use matrix_sdk::{Client, Room};
+use matrix_ui_serializable::CLIENT;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
 use tauri::{AppHandle, Manager, Runtime};
 use tracing::{trace, warn};
 
 use crate::PluginConfig;
 
+/// Get the Matrix client, or an error if the user isn't logged in yet.
+pub fn get_client() -> anyhow::Result<&'static Client> {
+    CLIENT
+        .get()
+        .ok_or(anyhow!("The Matrix client isn't initialized yet"))
+}
+
+/// Wait until the Matrix client is initialized.
+/// The wait happens in a blocking thread so we don't block the async runtime.
+pub async fn wait_for_client() -> Client {
+    tauri::async_runtime::spawn_blocking(|| CLIENT.wait().clone())
+        .await
+        .expect("couldn't wait for the Matrix client")
+}
+
+/// Get a room known by the client.
+pub fn get_room(room_id: &RoomId) -> anyhow::Result<Room> {
+    get_client()?
+        .get_room(room_id)
+        .ok_or(anyhow!("Room {room_id} not found"))
+}
+
+/// A state event, deserialized from either its sync or stripped form.
+#[derive(Deserialize)]
+pub struct StateEvent<C> {
+    pub sender: OwnedUserId,
+    pub content: C,
+}
+
+/// Get a state event with an empty state key, if it exists and is valid.
+pub async fn get_state_event<C: DeserializeOwned>(
+    room: &Room,
+    event_type: StateEventType,
+) -> anyhow::Result<Option<StateEvent<C>>> {
+    let Some(raw) = room.get_state_event(event_type, "").await? else {
+        return Ok(None);
+    };
+    let json = match &raw {
+        RawAnySyncOrStrippedState::Sync(raw) => raw.json().get(),
+        RawAnySyncOrStrippedState::Stripped(raw) => raw.json().get(),
+    };
+    Ok(serde_json::from_str(json).ok())
+}
+
+/// Add the thread and reply relations of the composer to a message, as the frontend
+/// does for the messages sent through `SendTextMessage`.
+pub async fn add_relations(
+    room: &Room,
+    content: RoomMessageEventContentWithoutRelation,
+    thread_root: Option<OwnedEventId>,
+    in_reply_to: Option<OwnedEventId>,
+) -> anyhow::Result<RoomMessageEventContent> {
+    let Some(event_id) = in_reply_to else {
+        return Ok(content.with_relation(
+            thread_root.map(|root| Relation::Thread(Thread::without_fallback(root))),
+        ));
+    };
+    let enforce_thread = if thread_root.is_some() {
+        EnforceThread::Threaded(ReplyWithinThread::Yes)
+    } else {
+        EnforceThread::Unthreaded
+    };
+    Ok(room
+        .make_reply_event(
+            content,
+            Reply {
+                event_id,
+                enforce_thread,
+            },
+        )
+        .await?)
+}
+
 pub fn get_app_dir_or_create_it<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<PathBuf> {
     let app_data_dir = app_handle.path().app_data_dir()?;
 
@@ -41,6 +127,16 @@ pub fn _get_temp_dir_or_create_it<R: Runtime>(
     Ok(temp_data_dir)
 }
 
+/// Write a file through a temporary sibling renamed over it, so a crash never leaves
+/// it half written.
+pub fn write_atomically(path: &Path, contents: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    std::fs::write(&temp_path, contents)?;
+    std::fs::rename(temp_path, path)?;
+    Ok(())
+}
+
 pub fn get_plugin_config<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<PluginConfig> {
     let plugin_config = app_handle.config().plugins.0.clone();
     let raw_matrix_config = plugin_config